@@ -33,6 +33,8 @@ pub const CONFIG_EXCEPTION_STACK_TOP: usize = 0x3f_8000_1000;
 pub const CONFIG_HEAP_BTM: usize = 0x10_0000_0000;
 pub const CONFIG_VIRTUAL_HEAP_BTM: usize = 0x20_0000_0000;
 pub const CONFIG_VIRTUAL_HEAP_TOP: usize = 0x20_1000_0000;
+pub const CONFIG_MMAP_BTM: usize = 0x30_0000_0000;
+pub const CONFIG_MMAP_TOP: usize = 0x38_0000_0000;
 
 pub const CONFIG_ELF_IMAGE: usize = 0x8000_0000;
 
@@ -64,12 +66,41 @@ pub mod syscall {
   pub const SYS_ITC_SEND: usize = 15;
   pub const SYS_ITC_CALL: usize = 16;
   pub const SYS_SERVER_REGISTER: usize = 17;
-  pub const SYS_SERVER_TID: usize = 18;
+  pub const SYS_SERVER_CAP: usize = 18;
   pub const SYS_SET_EXCEPTION_HANDLER: usize = 19;
   pub const SYS_GETC: usize = 20;
   pub const SYS_YIELD_TO: usize = 21;
   pub const SYS_REPLY_RECV: usize = 22;
-  pub const SYS_MAX: usize = 23;
+  pub const SYS_THREAD_SET_PRIORITY: usize = 23;
+  pub const SYS_THREAD_SET_AFFINITY: usize = 24;
+  pub const SYS_SLEEP: usize = 25;
+  pub const SYS_SLEEP_UNTIL: usize = 26;
+  pub const SYS_THREAD_STAT: usize = 27;
+  pub const SYS_THREAD_SET_REALTIME: usize = 28;
+  pub const SYS_ENDPOINT_CREATE: usize = 29;
+  pub const SYS_CAP_MINT: usize = 30;
+  pub const SYS_CAP_COPY: usize = 31;
+  pub const SYS_CAP_REVOKE: usize = 32;
+  pub const SYS_NOTIFICATION_CREATE: usize = 33;
+  pub const SYS_NOTIFICATION_SIGNAL: usize = 34;
+  pub const SYS_NOTIFICATION_WAIT: usize = 35;
+  pub const SYS_NOTIFICATION_POLL: usize = 36;
+  pub const SYS_NOTIFICATION_BIND: usize = 37;
+  pub const SYS_CHANNEL_CREATE: usize = 38;
+  pub const SYS_ITC_CALL_LONG: usize = 39;
+  pub const SYS_ITC_BUFFER_SET: usize = 40;
+  pub const SYS_SERVICE_REGISTER: usize = 41;
+  pub const SYS_SERVICE_LOOKUP: usize = 42;
+  pub const SYS_SERVICE_UNREGISTER: usize = 43;
+  pub const SYS_ADDRESS_SPACE_FORK: usize = 44;
+  pub const SYS_VMA_RESERVE: usize = 45;
+  pub const SYS_VMA_PROTECT: usize = 46;
+  pub const SYS_VMA_RELEASE: usize = 47;
+  pub const SYS_VMA_PAGER: usize = 48;
+  pub const SYS_MEM_ALLOC_DMA: usize = 49;
+  pub const SYS_ADDRESS_SPACE_LIMIT: usize = 50;
+  pub const SYS_ADDRESS_SPACE_STAT: usize = 51;
+  pub const SYS_MAX: usize = 52;
 
   pub mod error {
     pub const ERROR_INVARG: usize = 1;
@@ -80,10 +111,28 @@ pub mod syscall {
     pub const ERROR_HOLD_ON: usize = 6;
     pub const ERROR_OOR: usize = 7;
     pub const ERROR_PANIC: usize = 8;
+    pub const ERROR_TIMEOUT: usize = 9;
   }
 }
 
+pub mod itc {
+  /// timeout argument of `SYS_ITC_CALL`, `SYS_ITC_RECV` and `SYS_REPLY_RECV` to wait forever
+  pub const ITC_TIMEOUT_INFINITE: usize = 0;
+
+  /// mode bits of `SYS_ITC_CALL_LONG`: the callee reads the buffer
+  pub const ITC_LONG_READ: usize = 1 << 0;
+  /// the callee writes the buffer, copied back on reply unless granted
+  pub const ITC_LONG_WRITE: usize = 1 << 1;
+  /// map the pages of the buffer to the callee until reply instead of copying it
+  pub const ITC_LONG_GRANT: usize = 1 << 2;
+  /// maximum length of a long message buffer in bytes
+  pub const ITC_LONG_MAX: usize = 16 * crate::PAGE_SIZE;
+}
+
 pub mod server {
+  /// maximum length in bytes of a name passed to `SYS_SERVICE_REGISTER`
+  pub const SERVICE_NAME_MAX: usize = 64;
+
   pub const SERVER_BLK: usize = 0;
   pub const SERVER_REDOX_FS: usize = 1;
   pub const SERVER_TERMINAL: usize = 2;
@@ -96,11 +145,63 @@ pub mod server {
 pub mod thread {
   pub const THREAD_STATUS_RUNNABLE: usize = 1;
   pub const THREAD_STATUS_NOT_RUNNABLE: usize = 2;
+
+  /// number of priority bands, a larger value means a higher priority
+  pub const THREAD_PRIORITY_NUM: usize = 8;
+  pub const THREAD_PRIORITY_MIN: usize = 0;
+  pub const THREAD_PRIORITY_DEFAULT: usize = 2;
+  pub const THREAD_PRIORITY_SERVER: usize = 4;
+  pub const THREAD_PRIORITY_DRIVER: usize = 6;
+  pub const THREAD_PRIORITY_MAX: usize = THREAD_PRIORITY_NUM - 1;
+
+  /// core mask, bit `i` allows the thread to run on core `i`
+  pub const THREAD_AFFINITY_ANY: usize = usize::MAX;
+}
+
+pub mod cap {
+  /// call the endpoint or signal the notification
+  pub const CAP_RIGHT_SEND: usize = 1 << 0;
+  /// mint or copy the capability
+  pub const CAP_RIGHT_GRANT: usize = 1 << 1;
+  /// wait on, poll or bind the notification
+  pub const CAP_RIGHT_RECEIVE: usize = 1 << 2;
+  pub const CAP_RIGHT_ALL: usize = CAP_RIGHT_SEND | CAP_RIGHT_GRANT | CAP_RIGHT_RECEIVE;
+
+  /// sender reported by `itc_receive` when woken by the bound notification
+  pub const CAP_NOTIFICATION_SENDER: usize = 0;
+
+  /// badge of an unbadged capability, capabilities from `server_cap` are badged with the caller's asid
+  pub const CAP_BADGE_NONE: usize = 0;
+}
+
+pub mod channel {
+  /// maximum size of a channel region in pages
+  pub const CHANNEL_PAGES_MAX: usize = 16;
+}
+
+pub mod vma {
+  /// protection bits of `SYS_VMA_RESERVE` and `SYS_VMA_PROTECT`, none of them makes a guard area
+  pub const VMA_PROT_READ: usize = 1 << 0;
+  pub const VMA_PROT_WRITE: usize = 1 << 1;
+  pub const VMA_PROT_EXEC: usize = 1 << 2;
+}
+
+pub mod pager {
+  //! a fault on an area handed to a pager by `SYS_VMA_PAGER` is sent as a call through its endpoint
+  //! capability, with the badge of that capability and message `(address, access, asid, 0)`;
+  //! the pager maps the page with `SYS_MEM_MAP` and replies `a` of 0, then the faulting access is retried;
+  //! any other `a` makes the retried access raise an exception in the faulting thread
+
+  /// access bits of a fault message
+  pub const PAGER_FAULT_READ: usize = 1 << 0;
+  pub const PAGER_FAULT_WRITE: usize = 1 << 1;
+  pub const PAGER_FAULT_EXEC: usize = 1 << 2;
 }
 
 pub mod event {
   pub const EVENT_INTERRUPT: usize = 1;
   pub const EVENT_THREAD_EXIT: usize = 2;
+  pub const EVENT_DEADLINE_OVERRUN: usize = 3;
 }
 
 pub mod time {
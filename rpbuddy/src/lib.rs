@@ -0,0 +1,258 @@
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+pub const PAGE_SHIFT: usize = 12;
+pub const PAGE_SIZE: usize = 1 << PAGE_SHIFT;
+
+// largest block is 1 GB
+pub const MAX_ORDER: usize = 18;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Error {
+  InvalidArgument,
+  OutOfMemory,
+}
+
+// link of a free block, kept in its first page
+#[derive(Copy, Clone)]
+struct FreeBlock {
+  prev: usize,
+  next: usize,
+}
+
+// buddy allocator, a block of order `n` is `PAGE_SIZE << n` bytes aligned to its size
+pub struct PagePool {
+  range: Range<usize>,
+  base: usize,
+  // first free block of each order, 0 if none
+  heads: [usize; MAX_ORDER + 1],
+  // one bit per block of each order, set if the block is free
+  bitmaps: Vec<Vec<u64>>,
+  // address the links of a free block at a physical address are written at
+  kva: fn(usize) -> usize,
+}
+
+// smallest order holding `pages` pages
+pub fn order_of(pages: usize) -> usize {
+  pages.next_power_of_two().trailing_zeros() as usize
+}
+
+impl PagePool {
+  pub fn new(kva: fn(usize) -> usize) -> Self {
+    PagePool {
+      range: 0..0,
+      base: 0,
+      heads: [0; MAX_ORDER + 1],
+      bitmaps: Vec::new(),
+      kva,
+    }
+  }
+
+  fn node(&self, pa: usize) -> &'static mut FreeBlock {
+    unsafe { &mut *((self.kva)(pa) as *mut FreeBlock) }
+  }
+
+  // hand out `range`, the links of free blocks are written into the memory itself
+  pub fn init(&mut self, range: Range<usize>) {
+    assert_eq!(range.start % PAGE_SIZE, 0);
+    assert_eq!(range.end % PAGE_SIZE, 0);
+    self.base = range.start & !((PAGE_SIZE << MAX_ORDER) - 1);
+    self.range = range.clone();
+    for order in 0..=MAX_ORDER {
+      let blocks = (range.end - self.base) >> (PAGE_SHIFT + order);
+      self.bitmaps.push(alloc::vec![0; blocks / 64 + 1]);
+    }
+    let mut pa = range.start;
+    while pa < range.end {
+      let mut order = MAX_ORDER;
+      while pa & ((PAGE_SIZE << order) - 1) != 0 || pa + (PAGE_SIZE << order) > range.end {
+        order -= 1;
+      }
+      self.push(order, pa);
+      pa += PAGE_SIZE << order;
+    }
+  }
+
+  fn bit(&self, order: usize, pa: usize) -> (usize, u64) {
+    let i = (pa - self.base) >> (PAGE_SHIFT + order);
+    (i / 64, 1 << (i % 64))
+  }
+
+  fn is_free(&self, order: usize, pa: usize) -> bool {
+    let (word, mask) = self.bit(order, pa);
+    self.bitmaps[order][word] & mask != 0
+  }
+
+  fn push(&mut self, order: usize, pa: usize) {
+    let head = self.heads[order];
+    *self.node(pa) = FreeBlock { prev: 0, next: head };
+    if head != 0 {
+      self.node(head).prev = pa;
+    }
+    self.heads[order] = pa;
+    let (word, mask) = self.bit(order, pa);
+    self.bitmaps[order][word] |= mask;
+  }
+
+  fn remove(&mut self, order: usize, pa: usize) {
+    let FreeBlock { prev, next } = *self.node(pa);
+    if prev != 0 {
+      self.node(prev).next = next;
+    } else {
+      self.heads[order] = next;
+    }
+    if next != 0 {
+      self.node(next).prev = prev;
+    }
+    let (word, mask) = self.bit(order, pa);
+    self.bitmaps[order][word] &= !mask;
+  }
+
+  pub fn allocate(&mut self, order: usize) -> Result<usize, Error> {
+    if order > MAX_ORDER {
+      return Err(Error::InvalidArgument);
+    }
+    let mut o = order;
+    while o <= MAX_ORDER && self.heads[o] == 0 {
+      o += 1;
+    }
+    if o > MAX_ORDER {
+      return Err(Error::OutOfMemory);
+    }
+    let pa = self.heads[o];
+    self.remove(o, pa);
+    // Note: the upper halves of a split block are left free
+    while o > order {
+      o -= 1;
+      self.push(o, pa + (PAGE_SIZE << o));
+    }
+    Ok(pa)
+  }
+
+  pub fn free(&mut self, pa: usize, order: usize) -> Result<(), Error> {
+    if order > MAX_ORDER || pa & ((PAGE_SIZE << order) - 1) != 0 || pa < self.range.start || pa + (PAGE_SIZE << order) > self.range.end {
+      return Err(Error::InvalidArgument);
+    }
+    // Note: a block freed twice may have merged with its buddy meanwhile, look at the blocks holding it as well
+    if (order..=MAX_ORDER).any(|o| self.is_free(o, pa & !((PAGE_SIZE << o) - 1))) {
+      return Err(Error::InvalidArgument);
+    }
+    let mut pa = pa;
+    let mut order = order;
+    while order < MAX_ORDER {
+      let buddy = pa ^ (PAGE_SIZE << order);
+      if buddy < self.range.start || buddy + (PAGE_SIZE << order) > self.range.end || !self.is_free(order, buddy) {
+        break;
+      }
+      self.remove(order, buddy);
+      pa = pa.min(buddy);
+      order += 1;
+    }
+    self.push(order, pa);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const POOL_PAGES: usize = 16;
+
+  // pool over `POOL_PAGES` pages of host memory aligned to their size, links are written in place
+  fn pool(buffer: &mut Vec<u8>) -> (PagePool, usize) {
+    *buffer = vec![0; 2 * POOL_PAGES * PAGE_SIZE];
+    let align = POOL_PAGES * PAGE_SIZE;
+    let start = (buffer.as_ptr() as usize + align - 1) & !(align - 1);
+    let mut pool = PagePool::new(|pa| pa);
+    pool.init(start..start + POOL_PAGES * PAGE_SIZE);
+    (pool, start)
+  }
+
+  #[test]
+  fn order_rounds_up() {
+    assert_eq!(order_of(1), 0);
+    assert_eq!(order_of(2), 1);
+    assert_eq!(order_of(3), 2);
+    assert_eq!(order_of(4), 2);
+    assert_eq!(order_of(5), 3);
+  }
+
+  #[test]
+  fn allocate_splits_upper_halves() {
+    let mut buffer = Vec::new();
+    let (mut pool, start) = pool(&mut buffer);
+    assert_eq!(pool.allocate(0), Ok(start));
+    // Note: the split left one free block of each order 0 to 3 above the page
+    assert_eq!(pool.allocate(0), Ok(start + PAGE_SIZE));
+    assert_eq!(pool.allocate(1), Ok(start + 2 * PAGE_SIZE));
+    assert_eq!(pool.allocate(2), Ok(start + 4 * PAGE_SIZE));
+    assert_eq!(pool.allocate(3), Ok(start + 8 * PAGE_SIZE));
+    assert_eq!(pool.allocate(0), Err(Error::OutOfMemory));
+  }
+
+  #[test]
+  fn allocate_is_aligned_to_order() {
+    let mut buffer = Vec::new();
+    let (mut pool, start) = pool(&mut buffer);
+    assert_eq!(pool.allocate(0), Ok(start));
+    let pa = pool.allocate(2).unwrap();
+    assert_eq!(pa % (4 * PAGE_SIZE), 0);
+    assert_eq!(pool.allocate(MAX_ORDER + 1), Err(Error::InvalidArgument));
+    assert_eq!(pool.allocate(order_of(POOL_PAGES)), Err(Error::OutOfMemory));
+  }
+
+  #[test]
+  fn free_coalesces_buddies() {
+    let mut buffer = Vec::new();
+    let (mut pool, start) = pool(&mut buffer);
+    let pages: Vec<usize> = (0..POOL_PAGES).map(|_| pool.allocate(0).unwrap()).collect();
+    assert_eq!(pool.allocate(0), Err(Error::OutOfMemory));
+    for pa in pages.iter().rev() {
+      pool.free(*pa, 0).unwrap();
+    }
+    assert_eq!(pool.allocate(order_of(POOL_PAGES)), Ok(start));
+  }
+
+  #[test]
+  fn free_keeps_used_buddy_apart() {
+    let mut buffer = Vec::new();
+    let (mut pool, start) = pool(&mut buffer);
+    let first = pool.allocate(0).unwrap();
+    let second = pool.allocate(0).unwrap();
+    pool.free(second, 0).unwrap();
+    // Note: `first` is still used, so the free page can not form an order 1 block
+    assert_eq!(pool.allocate(1), Ok(start + 2 * PAGE_SIZE));
+    assert_eq!(pool.allocate(0), Ok(second));
+    pool.free(first, 0).unwrap();
+  }
+
+  #[test]
+  fn free_rejects_double_and_outside() {
+    let mut buffer = Vec::new();
+    let (mut pool, start) = pool(&mut buffer);
+    let pa = pool.allocate(0).unwrap();
+    pool.free(pa, 0).unwrap();
+    assert_eq!(pool.free(pa, 0), Err(Error::InvalidArgument));
+    assert_eq!(pool.free(start + POOL_PAGES * PAGE_SIZE, 0), Err(Error::InvalidArgument));
+    assert_eq!(pool.free(start + PAGE_SIZE, 1), Err(Error::InvalidArgument));
+  }
+
+  #[test]
+  fn free_rejects_double_after_merge() {
+    let mut buffer = Vec::new();
+    let (mut pool, start) = pool(&mut buffer);
+    let first = pool.allocate(0).unwrap();
+    let second = pool.allocate(0).unwrap();
+    pool.free(second, 0).unwrap();
+    pool.free(first, 0).unwrap();
+    // Note: both pages merged back into the whole pool, neither is free at order 0 any more
+    assert_eq!(pool.free(second, 0), Err(Error::InvalidArgument));
+    assert_eq!(pool.free(first, 0), Err(Error::InvalidArgument));
+    assert_eq!(pool.allocate(order_of(POOL_PAGES)), Ok(start));
+  }
+}
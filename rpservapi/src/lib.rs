@@ -14,9 +14,12 @@ pub mod mm {
 
 pub mod pm {
   pub mod action {
+    // command address and length, and the page limit of the process or 0 for the default,
+    // neither above the limit of the caller
     pub const SPAWN: usize = 1;
     pub const WAIT: usize = 2;
     pub const PS: usize = 3;
+    pub const FORK: usize = 4;
   }
 
   pub mod result {
@@ -24,6 +27,15 @@ pub mod pm {
     pub const HOLD_ON: usize = 1;
     pub const INVARG: usize = 2;
     pub const SPAWN_FAILED: usize = 3;
+    pub const FORK_FAILED: usize = 4;
+  }
+}
+
+pub mod fs {
+  // Note: file system requests are redox syscall packets, these are extra actions of trusted callers only
+  pub mod action {
+    // address space `b` is going away, its mappings are written back and removed
+    pub const CLIENT_EXIT: usize = usize::MAX;
   }
 }
 
@@ -39,3 +51,23 @@ pub mod blk {
     pub const ERR: usize = 1;
   }
 }
+
+pub mod terminal {
+  pub mod action {
+    // take keyboard input, `b` is nonzero if the client still holds the channel of its last claim
+    pub const CLAIM: usize = 1;
+    // address space `b` is going away, trusted callers only
+    pub const CLIENT_EXIT: usize = 2;
+  }
+
+  pub mod result {
+    pub const OK: usize = 0;
+    // a new channel is set up, `b` and `c` are its data and space notifications
+    pub const CONNECTED: usize = 1;
+    pub const ERR: usize = 2;
+  }
+
+  // where the input channel is mapped, in the client and in the terminal server
+  pub const CHANNEL_VA: usize = 0x38_0000_0000;
+  pub const CHANNEL_PAGES: usize = 1;
+}
@@ -1,8 +1,12 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use rpabi::PAGE_SIZE;
+use rpabi::itc::{ITC_LONG_MAX, ITC_LONG_WRITE};
 use rpabi::server::SERVER_REDOX_FS;
 
 use rpsyscall::message::Message;
 use redox::*;
-pub use redox::Stat;
+pub use redox::{MapFlags, Stat};
 
 pub struct File {
   handle: usize,
@@ -41,14 +45,23 @@ impl File {
   }
 
   pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-    let msg = Message {
-      a: SYS_READ,
-      b: self.handle,
-      c: buf.as_ptr() as usize,
-      d: buf.len(),
-    };
-    let msg = msg.call(SERVER_REDOX_FS).map_err(|_| Error::new(EIO))?;
-    Error::demux(msg.a)
+    let mut done = 0;
+    // Note: the buffer is passed as a long message, the kernel copies the reply back into it
+    for chunk in buf.chunks_mut(ITC_LONG_MAX) {
+      let msg = Message::new(SYS_READ, self.handle, 0, 0);
+      let msg = msg.call_long(SERVER_REDOX_FS, chunk.as_mut_ptr() as usize, chunk.len(), ITC_LONG_WRITE)
+        .map_err(|_| Error::new(EIO))?;
+      let n = match Error::demux(msg.a) {
+        Ok(n) => n,
+        Err(e) if done == 0 => return Err(e),
+        Err(_) => break,
+      };
+      done += n;
+      if n < chunk.len() {
+        break;
+      }
+    }
+    Ok(done)
   }
 
   pub fn write(&mut self, buf: &[u8]) -> Result<usize> {
@@ -104,6 +117,32 @@ impl File {
     Error::demux(msg.a).map(|_| stat)
   }
 
+  /// map `len` bytes from page aligned `offset`, `flags` holds `PROT_*` and one of `MAP_SHARED` and `MAP_PRIVATE`
+  ///
+  /// pages are read as they are touched, writes to a shared mapping reach the file on `Mmap::msync`,
+  /// when the mapping is dropped or when the file is closed
+  pub fn mmap(&self, offset: usize, len: usize, flags: MapFlags) -> Result<Mmap<'_>> {
+    let size = (len + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    let address = MMAP_BASE.fetch_add(size, Ordering::Relaxed);
+    if address + size > rpabi::CONFIG_MMAP_TOP {
+      return Err(Error::new(ENOMEM));
+    }
+    let map = Map {
+      offset,
+      size: len,
+      flags,
+      address,
+    };
+    let msg = Message {
+      a: SYS_FMAP,
+      b: self.handle,
+      c: &map as *const Map as usize,
+      d: core::mem::size_of::<Map>(),
+    };
+    let msg = msg.call(SERVER_REDOX_FS).map_err(|_| Error::new(EIO))?;
+    Error::demux(msg.a).map(|address| Mmap { file: self, address, len })
+  }
+
   pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
     let msg = Message {
       a: SYS_CHMOD,
@@ -128,6 +167,50 @@ impl Drop for File {
   }
 }
 
+// Note: addresses of unmapped ranges are not reused
+static MMAP_BASE: AtomicUsize = AtomicUsize::new(rpabi::CONFIG_MMAP_BTM);
+
+/// file range mapped by `File::mmap`, unmapped when dropped
+pub struct Mmap<'a> {
+  file: &'a File,
+  address: usize,
+  len: usize,
+}
+
+impl Mmap<'_> {
+  pub fn as_slice(&self) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(self.address as *const u8, self.len) }
+  }
+
+  pub fn as_mut_slice(&mut self) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(self.address as *mut u8, self.len) }
+  }
+
+  /// write the dirty pages of every shared mapping of the file back
+  pub fn msync(&self) -> Result<()> {
+    let msg = Message {
+      a: SYS_FSYNC,
+      b: self.file.handle,
+      c: 0,
+      d: 0,
+    };
+    let msg = msg.call(SERVER_REDOX_FS).map_err(|_| Error::new(EIO))?;
+    Error::demux(msg.a).map(|_| ())
+  }
+}
+
+impl Drop for Mmap<'_> {
+  fn drop(&mut self) {
+    let msg = Message {
+      a: SYS_FUNMAP,
+      b: self.address,
+      c: self.len,
+      d: 0,
+    };
+    let _ = msg.call(SERVER_REDOX_FS).map_err(|_| Error::new(EIO));
+  }
+}
+
 pub fn create_dir<P: AsRef<str>>(path: P) -> Result<()> {
   let msg = Message {
     a: SYS_OPEN,
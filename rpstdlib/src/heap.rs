@@ -1,15 +1,15 @@
 use buddy_system_allocator::LockedHeap;
 use rpabi::PAGE_SIZE;
+use rpabi::vma::{VMA_PROT_READ, VMA_PROT_WRITE};
 
-const HEAP_SIZE: usize = 16;
+// Note: reserved up front, pages are allocated on first touch
+const HEAP_SIZE: usize = 1024;
 
 #[global_allocator]
 static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
 
 pub fn init() {
-  for i in 0..HEAP_SIZE {
-    crate::mm::page_alloc(rpabi::CONFIG_HEAP_BTM + i * PAGE_SIZE).expect("heap alloc failed");
-  }
+  rpsyscall::vma_reserve(0, rpabi::CONFIG_HEAP_BTM, HEAP_SIZE * PAGE_SIZE, VMA_PROT_READ | VMA_PROT_WRITE).expect("heap reserve failed");
   unsafe {
     HEAP_ALLOCATOR.lock().init(rpabi::CONFIG_HEAP_BTM, HEAP_SIZE * PAGE_SIZE)
   }
@@ -1,8 +1,18 @@
 use rpsyscall::message::Message;
 
+const WAIT_POLL_INTERVAL_US: usize = 10000;
+
+// room left below the current frame for the call into the server
+const FORK_STACK_GAP: usize = 4096;
+
 pub fn exec(cmd: &str) -> Result<usize, &'static str> {
+  exec_with_limit(cmd, 0)
+}
+
+/// run `cmd` allowed at most `pages` pages of memory, 0 for the default of the process manager
+pub fn exec_with_limit(cmd: &str, pages: usize) -> Result<usize, &'static str> {
   let result = Message::new(
-    rpservapi::pm::action::SPAWN, cmd.as_ptr() as usize, cmd.len(), 0,
+    rpservapi::pm::action::SPAWN, cmd.as_ptr() as usize, cmd.len(), pages,
   ).call(rpabi::server::SERVER_PM).map_err(|_| "server call failed")?;
   match result.a {
     rpservapi::pm::result::OK => Ok(result.b),
@@ -20,7 +30,7 @@ pub fn wait(pid: usize) {
         break;
       }
       rpservapi::pm::result::HOLD_ON => {
-        rpsyscall::thread_yield();
+        rpsyscall::sleep(WAIT_POLL_INTERVAL_US);
       }
       _ => panic!("wait failed"),
     }
@@ -32,3 +42,18 @@ pub fn ps() {
     rpservapi::pm::action::PS, 0, 0, 0,
   ).call(rpabi::server::SERVER_PM);
 }
+
+/// run `entry(arg)` in a child process with a copy-on-write image of this one, returns its pid
+///
+/// the child starts on a copy of the calling thread's stack below the current frame, so `entry` must not return
+pub fn fork(entry: extern "C" fn(usize) -> !, arg: usize) -> Result<usize, &'static str> {
+  let marker = 0usize;
+  let sp = (&marker as *const usize as usize - FORK_STACK_GAP) & !0xf;
+  let result = Message::new(
+    rpservapi::pm::action::FORK, entry as usize, sp, arg,
+  ).call(rpabi::server::SERVER_PM).map_err(|_| "server call failed")?;
+  match result.a {
+    rpservapi::pm::result::OK => Ok(result.b),
+    _ => Err("fork failed"),
+  }
+}
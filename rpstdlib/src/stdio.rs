@@ -3,16 +3,41 @@ use alloc::vec::Vec;
 
 use spin::Mutex;
 
+use rpservapi::terminal::{CHANNEL_PAGES, CHANNEL_VA};
+use rpsyscall::channel::Consumer;
 use rpsyscall::message::Message;
 
+// end of the terminal input channel, set up by the first claim
+static INPUT: Mutex<Option<Consumer<u8>>> = Mutex::new(None);
+
+// next input byte, claiming input from the terminal whenever the channel runs empty
+// Note: another process claiming input meanwhile leaves this one waiting until it claims again
+fn getc() -> u8 {
+  let mut input = INPUT.lock();
+  loop {
+    if let Some(Ok(Some(c))) = input.as_ref().map(|i| i.try_pop()) {
+      return c;
+    }
+    let msg = Message::new(rpservapi::terminal::action::CLAIM, input.is_some() as usize, 0, 0);
+    let result = msg.call(rpabi::server::SERVER_TERMINAL).unwrap();
+    match result.a {
+      rpservapi::terminal::result::OK => {}
+      rpservapi::terminal::result::CONNECTED => {
+        *input = Some(unsafe { Consumer::new(CHANNEL_VA, CHANNEL_PAGES, result.b, result.c) });
+      }
+      _ => panic!("terminal claim failed"),
+    }
+    if let Some(Ok(c)) = input.as_ref().map(|i| i.pop()) {
+      return c;
+    }
+  }
+}
+
 pub fn getchar() -> u8 {
   loop {
-    let result = Message::default().call(rpabi::server::SERVER_TERMINAL).unwrap();
-    match result.a as u8 {
-      0 => rpsyscall::thread_yield(),
+    match getc() {
       8 | 127 => break 127, // backspace
-      b'\r' | 32..=126 => { // carriage return or visible
-        let c = result.a as u8;
+      c @ (b'\r' | 32..=126) => { // carriage return or visible
         print!("{}", c as char);
         break c;
       }
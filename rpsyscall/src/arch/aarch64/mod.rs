@@ -2,18 +2,19 @@ pub type Error = usize;
 
 macro_rules! syscall {
 
-    ($($name:ident($a:ident, $($b:ident, $($c:ident, $($d:ident, $($e:ident, $($f:ident, )?)?)?)?)?) -> ($($oa:ident:$ta:tt, $($ob:ident:$tb:tt, $($oc:ident:$tc:tt, $($od:ident:$td:tt, $($oe:ident:$te:tt, )?)?)?)?)?);)+) => {
+    ($($name:ident($a:ident, $($b:ident, $($c:ident, $($d:ident, $($e:ident, $($f:ident, $($g:ident, )?)?)?)?)?)?) -> ($($oa:ident:$ta:tt, $($ob:ident:$tb:tt, $($oc:ident:$tc:tt, $($od:ident:$td:tt, $($oe:ident:$te:tt, $($of:ident:$tf:tt, )?)?)?)?)?)?);)+) => {
         $(
             #[inline(always)]
             #[allow(unused_parens)]
             #[allow(dead_code)]
-            pub fn $name($a: usize, $($b: usize, $($c: usize, $($d: usize, $($e: usize, $($f: usize)?)?)?)?)?) -> Result<($($ta$(, $tb$(, $tc$(, $td$(, $te)?)?)?)?)?), Error> {
+            pub fn $name($a: usize, $($b: usize, $($c: usize, $($d: usize, $($e: usize, $($f: usize$(, $g: usize)?)?)?)?)?)?) -> Result<($($ta$(, $tb$(, $tc$(, $td$(, $te$(, $tf)?)?)?)?)?)?), Error> {
                 let ret: usize;
                 $(let $oa: $ta;
                 $(let $ob: $tb;
                 $(let $oc: $tc;
                 $(let $od: $td;
-                $(let $oe: $te;)?)?)?)?)?
+                $(let $oe: $te;
+                $(let $of: $tf;)?)?)?)?)?)?
                 unsafe {
                 core::arch::asm!(
                     "svc #0",
@@ -22,19 +23,21 @@ macro_rules! syscall {
                     $(in("x1") $c,
                     $(in("x2") $d,
                     $(in("x3") $e,
-                    $(in("x4") $f,)?)?)?)?)?
+                    $(in("x4") $f,
+                    $(in("x5") $g,)?)?)?)?)?)?
 
                     $(lateout("x0") $oa,
                     $(lateout("x1") $ob,
                     $(lateout("x2") $oc,
                     $(lateout("x3") $od,
-                    $(lateout("x4") $oe,)?)?)?)?)?
+                    $(lateout("x4") $oe,
+                    $(lateout("x5") $of,)?)?)?)?)?)?
                     lateout("x7") ret,
                     options(nostack),
                 );
                 }
                 if (ret == 0) {
-                    Ok(($($oa$(, $ob$(, $oc$(, $od$(, $oe)?)?)?)?)?))
+                    Ok(($($oa$(, $ob$(, $oc$(, $od$(, $oe$(, $of)?)?)?)?)?)?))
                 } else {
                     Err(ret)
                 }
@@ -54,8 +57,14 @@ syscall! {
     syscall_0_1(a, ) -> (oa: usize, );
     syscall_1_1(a, b, ) -> (oa: usize, );
     syscall_2_1(a, b, c, ) -> (oa: usize, );
+    syscall_3_1(a, b, c, d, ) -> (oa: usize, );
     syscall_0_2(a, ) -> (oa: usize, ob: usize, );
+    syscall_1_4(a, b, ) -> (oa: usize, ob: usize, oc: usize, od: usize, );
     syscall_4_1(a, b, c, d, e, ) -> (oa: usize, );
+    syscall_4_4(a, b, c, d, e, ) -> (oa: usize, ob: usize, oc: usize, od: usize, );
     syscall_0_5(a, ) -> (oa: usize, ob: usize, oc: usize, od: usize, oe: usize, );
     syscall_5_5(a, b, c, d, e, f, ) -> (oa: usize, ob: usize, oc: usize, od: usize, oe: usize, );
+    syscall_1_6(a, b, ) -> (oa: usize, ob: usize, oc: usize, od: usize, oe: usize, of: usize, );
+    syscall_6_5(a, b, c, d, e, f, g, ) -> (oa: usize, ob: usize, oc: usize, od: usize, oe: usize, );
+    syscall_6_6(a, b, c, d, e, f, g, ) -> (oa: usize, ob: usize, oc: usize, od: usize, oe: usize, of: usize, );
 }
\ No newline at end of file
@@ -0,0 +1,235 @@
+// single producer, single consumer ring over a region set up by `channel_create`
+// Note: the consumer sleeps on the data notification while the ring is empty, the producer signals it
+// when pushing into a drained ring, and the other way round the producer sleeps on the space notification
+// while the ring is full, the consumer signals it when popping from a full ring
+
+use core::marker::PhantomData;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicUsize, fence, Ordering};
+
+use rpabi::PAGE_SIZE;
+
+use crate::Error;
+
+/// bit signalled by the producer when data lands in a drained ring
+pub const CHANNEL_SIGNAL_DATA: usize = 1 << 0;
+
+/// bit signalled by the consumer when it frees slots of a full ring
+pub const CHANNEL_SIGNAL_SPACE: usize = 1 << 1;
+
+const CACHE_LINE_SIZE: usize = 64;
+
+// Note: head and tail sit on separate cache lines, elements start after the header
+const HEADER_SIZE: usize = 2 * CACHE_LINE_SIZE;
+
+#[repr(C)]
+struct Header {
+  // next element to pop, written by the consumer only
+  head: AtomicUsize,
+  _padding: [u8; CACHE_LINE_SIZE - size_of::<AtomicUsize>()],
+  // next element to push, written by the producer only
+  tail: AtomicUsize,
+}
+
+struct Ring<T> {
+  header: *const Header,
+  data: *mut T,
+  // power of two, indices are free running and wrap
+  capacity: usize,
+  // notifications of data pushed and of space freed
+  data: usize,
+  space: usize,
+  _marker: PhantomData<T>,
+}
+
+impl<T: Copy> Ring<T> {
+  unsafe fn new(va: usize, pages: usize, data: usize, space: usize) -> Self {
+    assert!(size_of::<T>() > 0 && core::mem::align_of::<T>() <= HEADER_SIZE);
+    assert_eq!(va % PAGE_SIZE, 0);
+    let slots = (pages * PAGE_SIZE - HEADER_SIZE) / size_of::<T>();
+    assert!(slots > 0);
+    Ring {
+      header: va as *const Header,
+      data: (va + HEADER_SIZE) as *mut T,
+      capacity: 1 << (usize::BITS - 1 - slots.leading_zeros()),
+      data,
+      space,
+      _marker: PhantomData,
+    }
+  }
+
+  fn header(&self) -> &Header {
+    unsafe { &*self.header }
+  }
+
+  fn slot(&self, index: usize) -> *mut T {
+    unsafe { self.data.add(index & (self.capacity - 1)) }
+  }
+}
+
+pub struct Producer<T> {
+  ring: Ring<T>,
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+
+impl<T: Copy> Producer<T> {
+  /// # Safety
+  ///
+  /// `va` must be a region of `pages` pages from `channel_create` shared with one `Consumer`,
+  /// and no other `Producer` may be created on it
+  pub unsafe fn new(va: usize, pages: usize, data: usize, space: usize) -> Self {
+    Producer {
+      ring: Ring::new(va, pages, data, space),
+    }
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.ring.capacity
+  }
+
+  /// push as many of `items` as fit, returns how many were pushed
+  pub fn try_push_slice(&self, items: &[T]) -> Result<usize, Error> {
+    let header = self.ring.header();
+    let tail = header.tail.load(Ordering::Relaxed);
+    let head = header.head.load(Ordering::Acquire);
+    let n = items.len().min(self.ring.capacity - tail.wrapping_sub(head));
+    if n == 0 {
+      return Ok(0);
+    }
+    for (i, item) in items[..n].iter().enumerate() {
+      unsafe { self.ring.slot(tail.wrapping_add(i)).write_volatile(*item); }
+    }
+    header.tail.store(tail.wrapping_add(n), Ordering::Release);
+    // Note: pairs with the fence in `Consumer::pop_slice`, either the consumer sees the new tail
+    // before it sleeps or we see that it drained the ring
+    fence(Ordering::SeqCst);
+    if header.head.load(Ordering::Relaxed) == tail {
+      crate::notification_signal(self.ring.data, CHANNEL_SIGNAL_DATA)?;
+    }
+    Ok(n)
+  }
+
+  /// push all of `items`, sleeping on the space notification while the ring is full
+  pub fn push_slice(&self, items: &[T]) -> Result<(), Error> {
+    let mut pushed = 0;
+    while pushed < items.len() {
+      match self.try_push_slice(&items[pushed..])? {
+        0 => {
+          // Note: pairs with the fence in `Consumer::try_pop_slice`
+          fence(Ordering::SeqCst);
+          let header = self.ring.header();
+          let used = header.tail.load(Ordering::Relaxed).wrapping_sub(header.head.load(Ordering::Relaxed));
+          if used == self.ring.capacity {
+            crate::notification_wait(self.ring.space)?;
+          }
+        }
+        n => pushed += n,
+      }
+    }
+    Ok(())
+  }
+
+  pub fn try_push(&self, item: T) -> Result<bool, Error> {
+    self.try_push_slice(&[item]).map(|n| n == 1)
+  }
+
+  pub fn push(&self, item: T) -> Result<(), Error> {
+    self.push_slice(&[item])
+  }
+}
+
+pub struct Consumer<T> {
+  ring: Ring<T>,
+}
+
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+impl<T: Copy> Consumer<T> {
+  /// # Safety
+  ///
+  /// `va` must be a region of `pages` pages from `channel_create` shared with one `Producer`,
+  /// and no other `Consumer` may be created on it
+  pub unsafe fn new(va: usize, pages: usize, data: usize, space: usize) -> Self {
+    Consumer {
+      ring: Ring::new(va, pages, data, space),
+    }
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.ring.capacity
+  }
+
+  /// pop as many elements as available into `buf`, returns how many were popped
+  pub fn try_pop_slice(&self, buf: &mut [T]) -> Result<usize, Error> {
+    let header = self.ring.header();
+    let head = header.head.load(Ordering::Relaxed);
+    let tail = header.tail.load(Ordering::Acquire);
+    let n = buf.len().min(tail.wrapping_sub(head));
+    if n == 0 {
+      return Ok(0);
+    }
+    for (i, item) in buf[..n].iter_mut().enumerate() {
+      *item = unsafe { self.ring.slot(head.wrapping_add(i)).read_volatile() };
+    }
+    header.head.store(head.wrapping_add(n), Ordering::Release);
+    self.freed(head)?;
+    Ok(n)
+  }
+
+  // wake the producer if the ring was full before popping from `head`
+  fn freed(&self, head: usize) -> Result<(), Error> {
+    // Note: pairs with the fence in `Producer::push_slice`, either the producer sees the new head
+    // before it sleeps or we see that it filled the ring
+    fence(Ordering::SeqCst);
+    let header = self.ring.header();
+    if header.tail.load(Ordering::Relaxed).wrapping_sub(head) == self.ring.capacity {
+      crate::notification_signal(self.ring.space, CHANNEL_SIGNAL_SPACE)?;
+    }
+    Ok(())
+  }
+
+  /// pop at least one element into non-empty `buf`, sleeping on the notification while the ring is empty
+  pub fn pop_slice(&self, buf: &mut [T]) -> Result<usize, Error> {
+    if buf.is_empty() {
+      return Ok(0);
+    }
+    loop {
+      let n = self.try_pop_slice(buf)?;
+      if n > 0 {
+        return Ok(n);
+      }
+      // Note: pairs with the fence in `Producer::try_push_slice`
+      fence(Ordering::SeqCst);
+      let header = self.ring.header();
+      if header.tail.load(Ordering::Relaxed) == header.head.load(Ordering::Relaxed) {
+        crate::notification_wait(self.ring.data)?;
+      }
+    }
+  }
+
+  pub fn try_pop(&self) -> Result<Option<T>, Error> {
+    let header = self.ring.header();
+    let head = header.head.load(Ordering::Relaxed);
+    if header.tail.load(Ordering::Acquire) == head {
+      return Ok(None);
+    }
+    let item = unsafe { self.ring.slot(head).read_volatile() };
+    header.head.store(head.wrapping_add(1), Ordering::Release);
+    self.freed(head)?;
+    Ok(Some(item))
+  }
+
+  pub fn pop(&self) -> Result<T, Error> {
+    loop {
+      if let Some(item) = self.try_pop()? {
+        return Ok(item);
+      }
+      fence(Ordering::SeqCst);
+      let header = self.ring.header();
+      if header.tail.load(Ordering::Relaxed) == header.head.load(Ordering::Relaxed) {
+        crate::notification_wait(self.ring.data)?;
+      }
+    }
+  }
+}
@@ -12,6 +12,8 @@ mod arch;
 #[path = "arch/riscv64/mod.rs"]
 mod arch;
 
+pub mod channel;
+
 pub fn null(dummy: usize) { let _ = syscall_1_0(SYS_NULL, dummy); }
 
 fn try_putc(c: char) -> Result<(), Error> {
@@ -77,13 +79,21 @@ pub fn event_wait(event_type: usize, event_num: usize) -> Result<usize, Error> {
   }
 }
 
-fn try_mem_alloc(asid: u16, va: usize, attr: usize) -> Result<(), Error> {
-  syscall_3_0(SYS_MEM_ALLOC, asid as usize, va, attr)
+fn try_mem_alloc(asid: u16, va: usize, attr: usize, size: usize) -> Result<usize, Error> {
+  syscall_4_1(SYS_MEM_ALLOC, asid as usize, va, attr, size)
 }
 
 pub fn mem_alloc(asid: u16, va: usize, attr: usize) -> Result<(), Error> {
-  match try_mem_alloc(asid, va, attr) {
-    Err(rpabi::syscall::error::ERROR_PANIC) => { try_mem_alloc(asid, va, attr) } // retry once
+  match try_mem_alloc(asid, va, attr, rpabi::PAGE_SIZE) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_mem_alloc(asid, va, attr, rpabi::PAGE_SIZE).map(|_| ()) } // retry once
+    x => x.map(|_| ())
+  }
+}
+
+// allocate with a 2 MiB or 1 GiB block if possible, returns the size mapped at `va`
+pub fn mem_alloc_hint(asid: u16, va: usize, attr: usize, size: usize) -> Result<usize, Error> {
+  match try_mem_alloc(asid, va, attr, size) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_mem_alloc(asid, va, attr, size) } // retry once
     x => x
   }
 }
@@ -110,6 +120,64 @@ pub fn mem_unmap(asid: u16, va: usize) -> Result<(), Error> {
   }
 }
 
+fn try_mem_alloc_dma(asid: u16, va: usize, len: usize, attr: usize) -> Result<usize, Error> {
+  syscall_4_1(SYS_MEM_ALLOC_DMA, asid as usize, va, len, attr)
+}
+
+// map physically contiguous memory at `[va, va + len)`, returns its physical address
+pub fn mem_alloc_dma(asid: u16, va: usize, len: usize, attr: usize) -> Result<usize, Error> {
+  match try_mem_alloc_dma(asid, va, len, attr) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_mem_alloc_dma(asid, va, len, attr) } // retry once
+    x => x
+  }
+}
+
+fn try_vma_reserve(asid: u16, va: usize, len: usize, prot: usize) -> Result<(), Error> {
+  syscall_4_0(SYS_VMA_RESERVE, asid as usize, va, len, prot)
+}
+
+/// reserve `[va, va + len)` with `rpabi::vma` protection bits, pages are allocated zeroed on first access
+pub fn vma_reserve(asid: u16, va: usize, len: usize, prot: usize) -> Result<(), Error> {
+  match try_vma_reserve(asid, va, len, prot) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_vma_reserve(asid, va, len, prot) } // retry once
+    x => x
+  }
+}
+
+fn try_vma_protect(asid: u16, va: usize, len: usize, prot: usize) -> Result<(), Error> {
+  syscall_4_0(SYS_VMA_PROTECT, asid as usize, va, len, prot)
+}
+
+pub fn vma_protect(asid: u16, va: usize, len: usize, prot: usize) -> Result<(), Error> {
+  match try_vma_protect(asid, va, len, prot) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_vma_protect(asid, va, len, prot) } // retry once
+    x => x
+  }
+}
+
+fn try_vma_release(asid: u16, va: usize, len: usize) -> Result<(), Error> {
+  syscall_3_0(SYS_VMA_RELEASE, asid as usize, va, len)
+}
+
+pub fn vma_release(asid: u16, va: usize, len: usize) -> Result<(), Error> {
+  match try_vma_release(asid, va, len) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_vma_release(asid, va, len) } // retry once
+    x => x
+  }
+}
+
+fn try_vma_pager(asid: u16, va: usize, len: usize, cap: usize) -> Result<(), Error> {
+  syscall_4_0(SYS_VMA_PAGER, asid as usize, va, len, cap)
+}
+
+/// send faults on reserved `[va, va + len)` of `asid` to endpoint `cap`, see `rpabi::pager`
+pub fn vma_pager(asid: u16, va: usize, len: usize, cap: usize) -> Result<(), Error> {
+  match try_vma_pager(asid, va, len, cap) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_vma_pager(asid, va, len, cap) } // retry once
+    x => x
+  }
+}
+
 fn try_address_space_alloc() -> Result<u16, Error> {
   syscall_0_1(SYS_ADDRESS_SPACE_ALLOC).map(|asid| asid as u16)
 }
@@ -154,42 +222,150 @@ pub fn address_space_destroy(asid: u16) -> Result<(), Error> {
   }
 }
 
-pub fn itc_receive() -> Result<(usize, usize, usize, usize, usize), Error> {
-  syscall_0_5(SYS_ITC_RECV)
+fn try_address_space_fork(asid: u16) -> Result<u16, Error> {
+  syscall_1_1(SYS_ADDRESS_SPACE_FORK, asid as usize).map(|asid| asid as u16)
+}
+
+/// duplicate address space `asid` with writable pages shared copy-on-write, returns the new asid
+pub fn address_space_fork(asid: u16) -> Result<u16, Error> {
+  match try_address_space_fork(asid) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_address_space_fork(asid) } // retry once
+    x => x
+  }
+}
+
+fn try_address_space_limit(asid: u16, pages: usize) -> Result<(), Error> {
+  syscall_2_0(SYS_ADDRESS_SPACE_LIMIT, asid as usize, pages)
+}
+
+/// allow address space `asid` at most `pages` pages of memory, `usize::MAX` for no limit
+pub fn address_space_limit(asid: u16, pages: usize) -> Result<(), Error> {
+  match try_address_space_limit(asid, pages) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_address_space_limit(asid, pages) } // retry once
+    x => x
+  }
+}
+
+fn try_address_space_stat(asid: u16) -> Result<(usize, usize, usize, usize), Error> {
+  syscall_1_4(SYS_ADDRESS_SPACE_STAT, asid as usize)
+}
+
+/// (anonymous pages, shared pages, device pages, page limit)
+pub fn address_space_stat(asid: u16) -> Result<(usize, usize, usize, usize), Error> {
+  match try_address_space_stat(asid) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_address_space_stat(asid) } // retry once
+    x => x
+  }
+}
+
+/// `timeout_us` of `ITC_TIMEOUT_INFINITE` waits forever, otherwise fails with `ERROR_TIMEOUT`
+///
+/// returns sender tid, message and the badge of the capability it called
+pub fn itc_receive(timeout_us: usize) -> Result<(usize, usize, usize, usize, usize, usize), Error> {
+  syscall_1_6(SYS_ITC_RECV, timeout_us)
 }
 
 pub fn itc_send(tid: usize, a: usize, b: usize, c: usize, d: usize) -> Result<(), Error> {
   syscall_5_0(SYS_ITC_SEND, tid as usize, a, b, c, d)
 }
 
-pub fn itc_call(tid: usize, a: usize, b: usize, c: usize, d: usize) -> Result<(usize, usize, usize, usize, usize), Error> {
-  syscall_5_5(SYS_ITC_CALL, tid as usize, a, b, c, d)
+pub fn itc_call(cap: usize, a: usize, b: usize, c: usize, d: usize, timeout_us: usize) -> Result<(usize, usize, usize, usize, usize), Error> {
+  syscall_6_5(SYS_ITC_CALL, cap, a, b, c, d, timeout_us)
+}
+
+/// call passing `[va, va + len)` to the server, which receives its own address and length of it as `c` and `d`
+///
+/// `mode` combines `ITC_LONG_READ`, `ITC_LONG_WRITE` and `ITC_LONG_GRANT`, the buffer must fit
+/// the receive buffer the server set with `itc_buffer_set`
+pub fn itc_call_long(cap: usize, a: usize, b: usize, va: usize, len: usize, mode: usize) -> Result<(usize, usize, usize, usize, usize), Error> {
+  syscall_6_5(SYS_ITC_CALL_LONG, cap, a, b, va, len, mode)
+}
+
+fn try_itc_buffer_set(copy_va: usize, copy_len: usize, window_va: usize, window_len: usize) -> Result<(), Error> {
+  syscall_4_0(SYS_ITC_BUFFER_SET, copy_va, copy_len, window_va, window_len)
+}
+
+/// set where long messages to current thread land: copies go to writable `[copy_va, copy_va + copy_len)`,
+/// grants are mapped at the unmapped, page aligned `[window_va, window_va + window_len)`
+pub fn itc_buffer_set(copy_va: usize, copy_len: usize, window_va: usize, window_len: usize) -> Result<(), Error> {
+  match try_itc_buffer_set(copy_va, copy_len, window_va, window_len) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_itc_buffer_set(copy_va, copy_len, window_va, window_len) } // retry once
+    x => x
+  }
 }
 
-fn try_server_register(server_id: usize) -> Result<(), Error> {
-  syscall_1_0(SYS_SERVER_REGISTER, server_id)
+fn try_server_register(server_id: usize, cap: usize) -> Result<(), Error> {
+  syscall_2_0(SYS_SERVER_REGISTER, server_id, cap)
 }
 
+/// publish a new endpoint received by current thread as `server_id`
 pub fn server_register(server_id: usize) -> Result<(), Error> {
-  match try_server_register(server_id) {
-    Err(rpabi::syscall::error::ERROR_PANIC) => { try_server_register(server_id) } // retry once
+  let cap = endpoint_create()?;
+  match try_server_register(server_id, cap) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_server_register(server_id, cap) } // retry once
     x => x
   }
 }
 
-fn server_tid(server_id: usize) -> Result<usize, Error> {
-  syscall_1_1(SYS_SERVER_TID, server_id)
+fn server_cap(server_id: usize) -> Result<usize, Error> {
+  syscall_1_1(SYS_SERVER_CAP, server_id)
 }
 
-pub fn server_tid_wait(server_id: usize) -> usize {
+/// capability to call `server_id`, waits until the server is registered
+pub fn server_cap_wait(server_id: usize) -> usize {
   loop {
-    if let Ok(tid) = server_tid(server_id) {
-      break tid;
+    if let Ok(cap) = server_cap(server_id) {
+      break cap;
     }
     thread_yield();
   }
 }
 
+fn try_service_register(name: &str, cap: usize) -> Result<(), Error> {
+  syscall_3_0(SYS_SERVICE_REGISTER, name.as_ptr() as usize, name.len(), cap)
+}
+
+/// publish a new endpoint received by current thread as `name`, only current thread may replace
+/// or unregister it and it is unregistered when current thread exits
+pub fn service_register(name: &str) -> Result<(), Error> {
+  let cap = endpoint_create()?;
+  match try_service_register(name, cap) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_service_register(name, cap) } // retry once
+    x => x
+  }
+}
+
+fn try_service_lookup(name: &str, wait: bool) -> Result<usize, Error> {
+  syscall_3_1(SYS_SERVICE_LOOKUP, name.as_ptr() as usize, name.len(), wait as usize)
+}
+
+/// capability to call the service registered as `name`, fails with `ERROR_INVARG` if there is none
+pub fn service_lookup(name: &str) -> Result<usize, Error> {
+  match try_service_lookup(name, false) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_service_lookup(name, false) } // retry once
+    x => x
+  }
+}
+
+/// capability to call the service registered as `name`, blocks until it is registered
+pub fn service_wait(name: &str) -> Result<usize, Error> {
+  match try_service_lookup(name, true) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_service_lookup(name, true) } // retry once
+    x => x
+  }
+}
+
+fn try_service_unregister(name: &str) -> Result<(), Error> {
+  syscall_2_0(SYS_SERVICE_UNREGISTER, name.as_ptr() as usize, name.len())
+}
+
+pub fn service_unregister(name: &str) -> Result<(), Error> {
+  match try_service_unregister(name) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_service_unregister(name) } // retry once
+    x => x
+  }
+}
+
 fn try_set_exception_handler(handler: usize) -> Result<(), Error> {
   syscall_1_0(SYS_SET_EXCEPTION_HANDLER, handler)
 }
@@ -209,8 +385,169 @@ pub fn yield_to(tid: usize) {
   let _ = syscall_1_1(SYS_YIELD_TO, tid);
 }
 
-pub fn itc_recv_reply(tid: usize, a: usize, b: usize, c: usize, d: usize) -> Result<(usize, usize, usize, usize, usize), Error> {
-  syscall_5_5(SYS_REPLY_RECV, tid as usize, a, b, c, d)
+pub fn itc_recv_reply(tid: usize, a: usize, b: usize, c: usize, d: usize, timeout_us: usize) -> Result<(usize, usize, usize, usize, usize, usize), Error> {
+  syscall_6_6(SYS_REPLY_RECV, tid as usize, a, b, c, d, timeout_us)
+}
+
+fn try_thread_set_priority(tid: usize, priority: usize) -> Result<(), Error> {
+  syscall_2_0(SYS_THREAD_SET_PRIORITY, tid, priority)
+}
+
+pub fn thread_set_priority(tid: usize, priority: usize) -> Result<(), Error> {
+  match try_thread_set_priority(tid, priority) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_thread_set_priority(tid, priority) } // retry once
+    x => x
+  }
+}
+
+fn try_thread_set_affinity(tid: usize, mask: usize) -> Result<(), Error> {
+  syscall_2_0(SYS_THREAD_SET_AFFINITY, tid, mask)
+}
+
+pub fn thread_set_affinity(tid: usize, mask: usize) -> Result<(), Error> {
+  match try_thread_set_affinity(tid, mask) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_thread_set_affinity(tid, mask) } // retry once
+    x => x
+  }
+}
+
+/// sleep for `us` microseconds
+pub fn sleep(us: usize) {
+  let _ = syscall_1_0(SYS_SLEEP, us);
+}
+
+/// sleep until `us` microseconds since boot
+pub fn sleep_until(us: usize) {
+  let _ = syscall_1_0(SYS_SLEEP_UNTIL, us);
+}
+
+fn try_thread_stat(tid: usize) -> Result<(usize, usize, usize, usize), Error> {
+  syscall_1_4(SYS_THREAD_STAT, tid)
+}
+
+/// (thread cycles, switch count, last core, address space cycles)
+/// cycles are spent in user mode, time in the kernel on behalf of the thread is not counted
+/// threads of other address spaces are visible to trusted only
+pub fn thread_stat(tid: usize) -> Result<(usize, usize, usize, usize), Error> {
+  match try_thread_stat(tid) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_thread_stat(tid) } // retry once
+    x => x
+  }
+}
+
+fn try_thread_set_realtime(tid: usize, period_us: usize, budget_us: usize, deadline_us: usize) -> Result<(), Error> {
+  syscall_4_0(SYS_THREAD_SET_REALTIME, tid, period_us, budget_us, deadline_us)
+}
+
+/// join EDF real-time class with `budget_us` per `period_us`, due `deadline_us` after each release;
+/// `period_us == 0` leaves the class
+pub fn thread_set_realtime(tid: usize, period_us: usize, budget_us: usize, deadline_us: usize) -> Result<(), Error> {
+  match try_thread_set_realtime(tid, period_us, budget_us, deadline_us) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_thread_set_realtime(tid, period_us, budget_us, deadline_us) } // retry once
+    x => x
+  }
+}
+
+fn try_endpoint_create() -> Result<usize, Error> {
+  syscall_0_1(SYS_ENDPOINT_CREATE)
+}
+
+/// create an endpoint received by current thread, returns a capability with all rights
+pub fn endpoint_create() -> Result<usize, Error> {
+  match try_endpoint_create() {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_endpoint_create() } // retry once
+    x => x
+  }
+}
+
+fn try_cap_mint(cap: usize, rights: usize, badge: usize) -> Result<usize, Error> {
+  syscall_3_1(SYS_CAP_MINT, cap, rights, badge)
+}
+
+/// derive a capability with a subset of `rights`, calls through it are reported to the receiver with `badge`
+///
+/// `CAP_BADGE_NONE` keeps the badge of `cap`, an already badged capability cannot be re-badged
+pub fn cap_mint(cap: usize, rights: usize, badge: usize) -> Result<usize, Error> {
+  match try_cap_mint(cap, rights, badge) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_cap_mint(cap, rights, badge) } // retry once
+    x => x
+  }
+}
+
+fn try_cap_copy(cap: usize, asid: u16) -> Result<usize, Error> {
+  syscall_2_1(SYS_CAP_COPY, cap, asid as usize)
+}
+
+/// derive a capability into address space `asid`, returns its slot there
+pub fn cap_copy(cap: usize, asid: u16) -> Result<usize, Error> {
+  match try_cap_copy(cap, asid) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_cap_copy(cap, asid) } // retry once
+    x => x
+  }
+}
+
+fn try_cap_revoke(cap: usize) -> Result<(), Error> {
+  syscall_1_0(SYS_CAP_REVOKE, cap)
+}
+
+/// invalidate all capabilities derived from `cap`
+pub fn cap_revoke(cap: usize) -> Result<(), Error> {
+  match try_cap_revoke(cap) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_cap_revoke(cap) } // retry once
+    x => x
+  }
+}
+
+fn try_notification_create() -> Result<usize, Error> {
+  syscall_0_1(SYS_NOTIFICATION_CREATE)
+}
+
+/// create a notification, returns a capability with all rights
+pub fn notification_create() -> Result<usize, Error> {
+  match try_notification_create() {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_notification_create() } // retry once
+    x => x
+  }
+}
+
+/// set `bits` in the notification, never blocks
+pub fn notification_signal(cap: usize, bits: usize) -> Result<(), Error> {
+  syscall_2_0(SYS_NOTIFICATION_SIGNAL, cap, bits)
+}
+
+/// block until the notification is signalled, returns and clears its bits
+pub fn notification_wait(cap: usize) -> Result<usize, Error> {
+  syscall_1_1(SYS_NOTIFICATION_WAIT, cap)
+}
+
+/// returns and clears the bits of the notification, 0 if not signalled
+pub fn notification_poll(cap: usize) -> Result<usize, Error> {
+  syscall_1_1(SYS_NOTIFICATION_POLL, cap)
+}
+
+fn try_notification_bind(cap: usize) -> Result<(), Error> {
+  syscall_1_0(SYS_NOTIFICATION_BIND, cap)
+}
+
+/// let signals wake current thread from `itc_receive`, reported as sender `CAP_NOTIFICATION_SENDER`
+pub fn notification_bind(cap: usize) -> Result<(), Error> {
+  match try_notification_bind(cap) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_notification_bind(cap) } // retry once
+    x => x
+  }
+}
+
+fn try_channel_create(va: usize, pages: usize, peer_asid: u16, peer_va: usize) -> Result<(usize, usize, usize, usize), Error> {
+  syscall_4_4(SYS_CHANNEL_CREATE, va, pages, peer_asid as usize, peer_va)
+}
+
+/// map `pages` shared pages at `va` of current address space and at `peer_va` of `peer_asid`,
+/// returns the slots of the data and space notifications in current address space, then in peer address space
+pub fn channel_create(va: usize, pages: usize, peer_asid: u16, peer_va: usize) -> Result<(usize, usize, usize, usize), Error> {
+  match try_channel_create(va, pages, peer_asid, peer_va) {
+    Err(rpabi::syscall::error::ERROR_PANIC) => { try_channel_create(va, pages, peer_asid, peer_va) } // retry once
+    x => x
+  }
 }
 
 pub mod message {
@@ -235,8 +572,21 @@ pub mod message {
     }
 
     pub fn receive() -> Result<(usize, Self), super::Error> {
-      super::itc_receive().map(|(tid, a, b, c, d)|
-        (tid, Message { a, b, c, d }))
+      Self::receive_timeout(rpabi::itc::ITC_TIMEOUT_INFINITE)
+    }
+
+    pub fn receive_timeout(timeout_us: usize) -> Result<(usize, Self), super::Error> {
+      Self::receive_badged_timeout(timeout_us).map(|(tid, _, msg)| (tid, msg))
+    }
+
+    /// also returns the badge of the capability the sender called, the asid of the sender for `server_cap`
+    pub fn receive_badged() -> Result<(usize, usize, Self), super::Error> {
+      Self::receive_badged_timeout(rpabi::itc::ITC_TIMEOUT_INFINITE)
+    }
+
+    pub fn receive_badged_timeout(timeout_us: usize) -> Result<(usize, usize, Self), super::Error> {
+      super::itc_receive(timeout_us).map(|(tid, a, b, c, d, badge)|
+        (tid, badge, Message { a, b, c, d }))
     }
 
     pub fn send_to(&self, tid: usize) -> Result<(), super::Error> {
@@ -247,20 +597,63 @@ pub mod message {
     }
     
     pub fn reply_recv(&self, tid: usize) -> Result<(usize, Self), super::Error> {
-      super::itc_recv_reply(tid, self.a, self.b, self.c, self.d).map(|(tid, a, b, c, d)|
-        (tid, Message { a, b, c, d }))
+      self.reply_recv_timeout(tid, rpabi::itc::ITC_TIMEOUT_INFINITE)
+    }
+
+    pub fn reply_recv_timeout(&self, tid: usize, timeout_us: usize) -> Result<(usize, Self), super::Error> {
+      self.reply_recv_badged_timeout(tid, timeout_us).map(|(tid, _, msg)| (tid, msg))
+    }
+
+    pub fn reply_recv_badged(&self, tid: usize) -> Result<(usize, usize, Self), super::Error> {
+      self.reply_recv_badged_timeout(tid, rpabi::itc::ITC_TIMEOUT_INFINITE)
+    }
+
+    pub fn reply_recv_badged_timeout(&self, tid: usize, timeout_us: usize) -> Result<(usize, usize, Self), super::Error> {
+      super::itc_recv_reply(tid, self.a, self.b, self.c, self.d, timeout_us).map(|(tid, a, b, c, d, badge)|
+        (tid, badge, Message { a, b, c, d }))
     }
     
     pub fn call(&self, server_id: usize) -> Result<Self, super::Error> {
-      use rpabi::syscall::error::{ERROR_HOLD_ON, ERROR_PANIC};
-      let server_tid = super::server_tid_wait(server_id);
+      self.call_timeout(server_id, rpabi::itc::ITC_TIMEOUT_INFINITE)
+    }
+
+    /// call passing `a`, `b` and a buffer, see `itc_call_long`
+    pub fn call_long(&self, server_id: usize, va: usize, len: usize, mode: usize) -> Result<Self, super::Error> {
+      use rpabi::syscall::error::ERROR_PANIC;
+      let server_cap = super::server_cap_wait(server_id);
       loop {
-        match super::itc_call(server_tid, self.a, self.b, self.c, self.d) {
+        match super::itc_call_long(server_cap, self.a, self.b, va, len, mode) {
           Ok((_, a, b, c, d)) => {
             break Ok(Message { a, b, c, d });
           }
-          Err(ERROR_HOLD_ON) => {
-            super::thread_yield();
+          Err(ERROR_PANIC) => {
+            // retry
+          }
+          Err(e) => {
+            break Err(e);
+          }
+        }
+      }
+    }
+
+    /// fails with `ERROR_TIMEOUT` if no reply arrives within `timeout_us`
+    pub fn call_timeout(&self, server_id: usize, timeout_us: usize) -> Result<Self, super::Error> {
+      self.call_cap_timeout(super::server_cap_wait(server_id), timeout_us)
+    }
+
+    /// call the service registered as `name`, waiting until it is registered
+    pub fn call_service(&self, name: &str) -> Result<Self, super::Error> {
+      self.call_cap_timeout(super::service_wait(name)?, rpabi::itc::ITC_TIMEOUT_INFINITE)
+    }
+
+    /// call through capability `server_cap`, fails with `ERROR_TIMEOUT` if no reply arrives within `timeout_us`
+    pub fn call_cap_timeout(&self, server_cap: usize, timeout_us: usize) -> Result<Self, super::Error> {
+      use rpabi::syscall::error::ERROR_PANIC;
+      // Note: kernel queues the call while server is busy
+      loop {
+        match super::itc_call(server_cap, self.a, self.b, self.c, self.d, timeout_us) {
+          Ok((_, a, b, c, d)) => {
+            break Ok(Message { a, b, c, d });
           }
           Err(ERROR_PANIC) => {
             // retry
@@ -272,4 +665,4 @@ pub mod message {
       }
     }
   }
-}
\ No newline at end of file
+}
@@ -127,6 +127,14 @@ impl ContextFrameTrait for Aarch64ContextFrame {
             self.gpr[3] = *d as u64;
             self.gpr[4] = *e as u64;
           }
+          SyscallOutRegisters::Hexad(a, b, c, d, e, f) => {
+            self.gpr[0] = *a as u64;
+            self.gpr[1] = *b as u64;
+            self.gpr[2] = *c as u64;
+            self.gpr[3] = *d as u64;
+            self.gpr[4] = *e as u64;
+            self.gpr[5] = *f as u64;
+          }
         }
       }
       Err(e) => {
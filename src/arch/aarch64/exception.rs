@@ -60,15 +60,21 @@ unsafe extern "C" fn lower_aarch64_irq(ctx: *mut ContextFrame) {
   use crate::lib::interrupt::*;
   let core = crate::lib::cpu::cpu();
   core.set_context(ctx);
-  use crate::driver::{INTERRUPT_CONTROLLER, gic::INT_TIMER};
+  use crate::driver::{INTERRUPT_CONTROLLER, gic::{INT_IPI, INT_TIMER}};
   let irq = INTERRUPT_CONTROLLER.fetch();
   match irq {
     Some(INT_TIMER) => {
       crate::lib::timer::interrupt();
     }
+    // Note: acknowledged software generated interrupts carry the sending core above the interrupt id
+    Some(i) if i & 0x3ff == INT_IPI => {
+      crate::mm::tlb::interrupt();
+      core.reschedule();
+    }
     Some(i) => {
       if i >= 32 {
         crate::lib::interrupt::interrupt(i);
+        core.reschedule();
       } else {
         panic!("GIC unhandled SGI PPI")
       }
@@ -42,14 +42,37 @@ impl ArchTrait for Aarch64Arch {
   }
 
   fn invalidate_tlb() {
+    // Note: local core only, other cores are reached by `crate::mm::tlb::shootdown`
     unsafe {
       core::arch::asm!("dsb ishst");
-      core::arch::asm!("tlbi vmalle1is");
+      core::arch::asm!("tlbi vmalle1");
+      core::arch::asm!("dsb nsh");
+      core::arch::asm!("isb");
+    }
+  }
+
+  // Note: inner shareable, broadcast to every core
+  fn invalidate_tlb_page(va: usize, asid: usize) {
+    let operand = (asid << 48) | ((va >> PAGE_SHIFT) & ((1 << 44) - 1));
+    unsafe {
+      core::arch::asm!("dsb ishst");
+      core::arch::asm!("tlbi vale1is, {}", in(reg) operand);
+      core::arch::asm!("dsb ish");
+      core::arch::asm!("isb");
+    }
+  }
+
+  fn invalidate_tlb_asid(asid: usize) {
+    unsafe {
+      core::arch::asm!("dsb ishst");
+      core::arch::asm!("tlbi aside1is, {}", in(reg) asid << 48);
       core::arch::asm!("dsb ish");
       core::arch::asm!("isb");
     }
   }
 
+  const TLB_BROADCAST: bool = true;
+
   fn wait_for_interrupt() {
     aarch64_cpu::asm::wfi();
   }
@@ -62,7 +85,26 @@ impl ArchTrait for Aarch64Arch {
     FAR_EL1.get() as usize
   }
 
+  fn fault_access() -> usize {
+    // Note: WnR bit of the data abort syndrome
+    const ISS_WNR: u64 = 1 << 6;
+    if ESR_EL1.matches_all(ESR_EL1::EC::InstrAbortLowerEL) {
+      rpabi::pager::PAGER_FAULT_EXEC
+    } else if ESR_EL1.read(ESR_EL1::ISS) & ISS_WNR != 0 {
+      rpabi::pager::PAGER_FAULT_WRITE
+    } else {
+      rpabi::pager::PAGER_FAULT_READ
+    }
+  }
+
   fn core_id() -> CoreId {
     MPIDR_EL1.get() as usize & (BOARD_CORE_NUMBER - 1)
   }
+
+  fn asid_bits() -> usize {
+    match ID_AA64MMFR0_EL1.read_as_enum(ID_AA64MMFR0_EL1::ASIDBits) {
+      Some(ID_AA64MMFR0_EL1::ASIDBits::Value::Bits_16) => 16,
+      _ => 8,
+    }
+  }
 }
\ No newline at end of file
@@ -9,7 +9,8 @@ use tock_registers::interfaces::Writeable;
 use crate::arch::*;
 use crate::lib::traits::*;
 use crate::mm::{Frame, PhysicalFrame};
-use crate::mm::page_table::{Entry, EntryAttribute, Error, PageTableEntryAttrTrait, PageTableTrait};
+use crate::mm::page_table::{Entry, EntryAttribute, Error, PageTableEntryAttrTrait, PageTableTrait, Resident};
+use crate::mm::tlb::Tlb;
 
 pub const PAGE_TABLE_L1_SHIFT: usize = 30;
 pub const PAGE_TABLE_L2_SHIFT: usize = 21;
@@ -20,6 +21,8 @@ pub struct Aarch64PageTable {
   directory: PhysicalFrame,
   pages: Mutex<Vec<PhysicalFrame>>,
   user_pages: Mutex<BTreeMap<usize, Frame>>,
+  resident: Resident,
+  tlb: Tlb,
 }
 
 #[repr(transparent)]
@@ -66,6 +69,27 @@ impl ArchPageTableEntryTrait for Aarch64PageTableEntry {
   }
 }
 
+impl Aarch64PageTableEntry {
+  // level-1 or level-2 entry mapping a block instead of pointing to a table
+  fn block(&self) -> bool {
+    self.valid() && self.0 & 0b10 == 0
+  }
+
+  fn make_block(entry: Entry) -> Self {
+    Aarch64PageTableEntry(Aarch64PageTableEntry::from(entry).0 & !0b10)
+  }
+
+  fn empty(&self) -> bool {
+    (0..PAGE_SIZE / MACHINE_SIZE).all(|i| !self.entry(i).valid())
+  }
+
+  // entry of the page holding `va` in the block or page mapped by `self`
+  fn leaf(&self, va: usize, size: usize) -> Entry {
+    let entry = Entry::from(*self);
+    Entry::sized(entry.attribute(), entry.pa() + (va & (size - 1) & !(PAGE_SIZE - 1)), size)
+  }
+}
+
 trait Index {
   fn l1x(&self) -> usize;
   fn l2x(&self) -> usize;
@@ -129,12 +153,59 @@ impl core::convert::From<Entry> for Aarch64PageTableEntry {
   }
 }
 
+impl Aarch64PageTable {
+  // table `index` of `table` points to, allocated if missing
+  fn next_table(&self, table: Aarch64PageTableEntry, index: usize) -> Result<Aarch64PageTableEntry, Error> {
+    let e = table.entry(index);
+    if e.block() {
+      return Err(ERROR_INVARG);
+    }
+    if e.valid() {
+      return Ok(e);
+    }
+    let frame = crate::mm::page_pool::page_alloc()?;
+    frame.zero();
+    let e = Aarch64PageTableEntry::make_table(frame.pa());
+    let mut pages = self.pages.lock();
+    pages.push(frame);
+    table.set_entry(index, e);
+    Ok(e)
+  }
+
+  // map a block at `index` of `table` for `va`, replacing a block or a table with nothing mapped,
+  // the entry replaced is shot down and the frame of a table replaced freed after
+  fn make_leaf(&self, table: Aarch64PageTableEntry, index: usize, va: usize, pa: usize, attr: EntryAttribute) -> Result<(), Error> {
+    let current = table.entry(index);
+    if current.valid() && !current.block() && !current.empty() {
+      return Err(ERROR_INVARG);
+    }
+    table.set_entry(index, Aarch64PageTableEntry::make_block(Entry::new(attr, pa)));
+    if !current.valid() {
+      return Ok(());
+    }
+    // Note: a block is cached as one entry covering `va`, a table by the walks through it
+    if current.block() {
+      crate::mm::tlb::shootdown_page(self, va);
+      return Ok(());
+    }
+    let frame = {
+      let mut pages = self.pages.lock();
+      pages.iter().position(|frame| frame.pa() == current.to_pa()).map(|i| pages.swap_remove(i))
+    };
+    crate::mm::tlb::shootdown(self);
+    drop(frame);
+    Ok(())
+  }
+}
+
 impl PageTableTrait for Aarch64PageTable {
   fn new(directory: PhysicalFrame) -> Self {
     Aarch64PageTable {
       directory,
       pages: Mutex::new(Vec::new()),
       user_pages: Mutex::new(BTreeMap::new()),
+      resident: Resident::default(),
+      tlb: Tlb::default(),
     }
   }
 
@@ -143,25 +214,25 @@ impl PageTableTrait for Aarch64PageTable {
   }
 
   fn map(&self, va: usize, pa: usize, attr: EntryAttribute) -> Result<(), Error> {
+    self.map_block(va, pa, PAGE_SIZE, attr)
+  }
+
+  fn map_block(&self, va: usize, pa: usize, size: usize, attr: EntryAttribute) -> Result<(), Error> {
+    if va % size != 0 || pa % size != 0 {
+      return Err(ERROR_INVARG);
+    }
     let directory = Aarch64PageTableEntry::from_pa(self.directory.pa());
-    let mut l1e = directory.entry(va.l1x());
-    if !l1e.valid() {
-      let frame = crate::mm::page_pool::page_alloc()?;
-      frame.zero();
-      l1e = Aarch64PageTableEntry::make_table(frame.pa());
-      let mut pages = self.pages.lock();
-      pages.push(frame);
-      directory.set_entry(va.l1x(), l1e);
+    if size == 1 << PAGE_TABLE_L1_SHIFT {
+      return self.make_leaf(directory, va.l1x(), va, pa, attr);
     }
-    let mut l2e = l1e.entry(va.l2x());
-    if !l2e.valid() {
-      let frame = crate::mm::page_pool::page_alloc()?;
-      frame.zero();
-      l2e = Aarch64PageTableEntry::make_table(frame.pa());
-      let mut pages = self.pages.lock();
-      pages.push(frame);
-      l1e.set_entry(va.l2x(), l2e);
+    let l1e = self.next_table(directory, va.l1x())?;
+    if size == 1 << PAGE_TABLE_L2_SHIFT {
+      return self.make_leaf(l1e, va.l2x(), va, pa, attr);
+    }
+    if size != PAGE_SIZE {
+      return Err(ERROR_INVARG);
     }
+    let l2e = self.next_table(l1e, va.l2x())?;
     l2e.set_entry(va.l3x(), Aarch64PageTableEntry::from(Entry::new(attr, pa)));
     Ok(())
   }
@@ -170,23 +241,49 @@ impl PageTableTrait for Aarch64PageTable {
     let directory = Aarch64PageTableEntry::from_pa(self.directory.pa());
     let l1e = directory.entry(va.l1x());
     assert!(l1e.valid());
+    if l1e.block() {
+      directory.set_entry(va.l1x(), Aarch64PageTableEntry(0));
+      return;
+    }
     let l2e = l1e.entry(va.l2x());
     assert!(l2e.valid());
+    if l2e.block() {
+      l1e.set_entry(va.l2x(), Aarch64PageTableEntry(0));
+      return;
+    }
     l2e.set_entry(va.l3x(), Aarch64PageTableEntry(0));
   }
 
   fn insert_page(&self, va: usize, user_frame: crate::mm::Frame, attr: EntryAttribute) -> Result<(), Error> {
     let pa = user_frame.pa();
+    let size = user_frame.size();
+    let mut replaced_attr = None;
     if let Some(p) = self.lookup_page(va) {
+      // Note: a page is not mapped into a block nor a block over pages, the old mapping would be partly lost
+      if p.size() != size {
+        return Err(ERROR_INVARG);
+      }
       if p.pa() != pa {
         // replace mapped frame
         self.remove_page(va)?;
+      } else {
+        replaced_attr = Some(p.attribute());
       }
     }
-    self.map(va, pa, attr)?;
+    self.map_block(va, pa, size, attr)?;
+    self.resident.insert(&user_frame, attr);
     let mut user_frames = self.user_pages.lock();
-    user_frames.insert(va, user_frame);
-    crate::arch::Arch::invalidate_tlb();
+    let replaced = user_frames.insert(va, user_frame);
+    if let Some(replaced) = &replaced {
+      // Note: only the attribute of a mapping kept in place changed
+      self.resident.remove(replaced, replaced_attr.unwrap_or(attr));
+    }
+    drop(user_frames);
+    // Note: a new entry was invalid before, a replaced frame was shot down by `remove_page`
+    //       and a replaced block by `map_block`
+    if replaced_attr.is_some() && size == PAGE_SIZE {
+      crate::mm::tlb::shootdown_page(self, va);
+    }
     Ok(())
   }
 
@@ -196,13 +293,19 @@ impl PageTableTrait for Aarch64PageTable {
     if !l1e.valid() {
       return None;
     }
+    if l1e.block() {
+      return Some(l1e.leaf(va, 1 << PAGE_TABLE_L1_SHIFT));
+    }
     let l2e = l1e.entry(va.l2x());
     if !l2e.valid() {
       return None;
     }
+    if l2e.block() {
+      return Some(l2e.leaf(va, 1 << PAGE_TABLE_L2_SHIFT));
+    }
     let l3e = l2e.entry(va.l3x());
     if l3e.valid() {
-      Some(Entry::from(l3e))
+      Some(l3e.leaf(va, PAGE_SIZE))
     } else {
       None
     }
@@ -210,21 +313,44 @@ impl PageTableTrait for Aarch64PageTable {
 
   fn lookup_user_page(&self, va: usize) -> Option<Frame> {
     let user_frames = self.user_pages.lock();
-    user_frames.get(&va).map(|x| x.clone())
+    match user_frames.range(..=va).next_back() {
+      Some((start, frame)) if va - start < frame.size() => Some(frame.clone()),
+      _ => None,
+    }
+  }
+
+  fn user_page_list(&self) -> Vec<(usize, Frame)> {
+    let user_frames = self.user_pages.lock();
+    user_frames.iter().map(|(va, frame)| (*va, frame.clone())).collect()
   }
 
   fn remove_page(&self, va: usize) -> Result<(), Error> {
-    if let Some(_) = self.lookup_page(va) {
+    if let Some(entry) = self.lookup_page(va) {
+      let va = va & !(entry.size() - 1);
       self.unmap(va);
       let mut user_frames = self.user_pages.lock();
-      user_frames.remove(&va);
-      // crate::arch::Arch::invalidate_tlb();
+      let frame = user_frames.remove(&va);
+      if let Some(frame) = &frame {
+        self.resident.remove(frame, entry.attribute());
+      }
+      drop(user_frames);
+      // Note: the frame is freed once no core can reach it through its TLB
+      crate::mm::tlb::shootdown_page(self, va);
+      drop(frame);
       Ok(())
     } else {
       Err(ERROR_INVARG)
     }
   }
 
+  fn resident(&self) -> &Resident {
+    &self.resident
+  }
+
+  fn tlb(&self) -> &Tlb {
+    &self.tlb
+  }
+
   fn recursive_map(&self, va: usize) {
     assert_eq!(va % (1 << PAGE_TABLE_L1_SHIFT), 0);
     let directory = Aarch64PageTableEntry::from_pa(self.directory.pa());
@@ -235,9 +361,12 @@ impl PageTableTrait for Aarch64PageTable {
     )));
   }
 
-  fn install_user_page_table(base: usize, _asid: AddressSpaceId) {
+  fn install_user_page_table(base: usize, asid: AddressSpaceId) {
     use aarch64_cpu::registers::TTBR0_EL1;
-    TTBR0_EL1.write(TTBR0_EL1::BADDR.val((base >> 1) as u64));
-    crate::arch::Arch::invalidate_tlb();
+    // Note: TCR_EL1.A1 selects the ASID of TTBR0, entries of other ASIDs stay valid in the TLB
+    TTBR0_EL1.write(TTBR0_EL1::ASID.val(asid as u64) + TTBR0_EL1::BADDR.val((base >> 1) as u64));
+    unsafe {
+      core::arch::asm!("isb");
+    }
   }
 }
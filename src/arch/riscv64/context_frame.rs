@@ -156,6 +156,14 @@ impl crate::lib::traits::ContextFrameTrait for Riscv64ContextFrame {
             self.gpr[13] = *d as u64;
             self.gpr[14] = *e as u64;
           }
+          SyscallOutRegisters::Hexad(a, b, c, d, e, f) => {
+            self.gpr[10] = *a as u64;
+            self.gpr[11] = *b as u64;
+            self.gpr[12] = *c as u64;
+            self.gpr[13] = *d as u64;
+            self.gpr[14] = *e as u64;
+            self.gpr[15] = *f as u64;
+          }
         }
       }
       Err(e) => {
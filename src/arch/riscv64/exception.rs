@@ -55,13 +55,18 @@ unsafe extern "C" fn exception_entry(ctx: *mut ContextFrame) {
   }
   if irq {
     match code {
-      INTERRUPT_SUPERVISOR_SOFTWARE => panic!("Interrupt::SupervisorSoft"),
+      INTERRUPT_SUPERVISOR_SOFTWARE => {
+        crate::driver::ipi::clear();
+        crate::mm::tlb::interrupt();
+        core.reschedule();
+      }
       INTERRUPT_SUPERVISOR_TIMER => crate::lib::timer::interrupt(),
       INTERRUPT_SUPERVISOR_EXTERNAL => {
         let plic = &crate::driver::INTERRUPT_CONTROLLER;
         if let Some(int) = plic.fetch() {
           crate::lib::interrupt::interrupt(int);
           plic.finish(int);
+          core.reschedule();
         } else {
           warn!("PLIC report no irq");
         }
@@ -1,6 +1,6 @@
 use core::mem::size_of;
 use riscv::{asm::*, regs::*};
-use tock_registers::interfaces::Readable;
+use tock_registers::interfaces::{Readable, Writeable};
 
 use crate::lib::traits::*;
 
@@ -43,6 +43,21 @@ impl ArchTrait for Riscv64Arch {
     riscv::barrier::sfence_vma_all();
   }
 
+  // Note: current hart only, other harts are reached by `crate::mm::tlb::shootdown`
+  fn invalidate_tlb_page(va: usize, asid: usize) {
+    unsafe {
+      core::arch::asm!("sfence.vma {}, {}", in(reg) va, in(reg) asid);
+    }
+  }
+
+  fn invalidate_tlb_asid(asid: usize) {
+    unsafe {
+      core::arch::asm!("sfence.vma zero, {}", in(reg) asid);
+    }
+  }
+
+  const TLB_BROADCAST: bool = false;
+
   fn wait_for_interrupt() {
     wfi();
   }
@@ -55,10 +70,30 @@ impl ArchTrait for Riscv64Arch {
     STVAL.get() as usize
   }
 
+  fn fault_access() -> usize {
+    // Note: instruction page fault, store access fault and store page fault
+    match SCAUSE.get() {
+      12 => rpabi::pager::PAGER_FAULT_EXEC,
+      7 | 15 => rpabi::pager::PAGER_FAULT_WRITE,
+      _ => rpabi::pager::PAGER_FAULT_READ,
+    }
+  }
+
   fn core_id() -> CoreId {
     // Note: a pointer to hart_id is stored in sscratch
     unsafe {
       ((SSCRATCH.get() as usize) as *const usize).read()
     }
   }
+
+  fn asid_bits() -> usize {
+    // Note: unimplemented ASID bits of satp are hardwired to zero, set them all and count the ones left
+    const SATP_ASID_SHIFT: u64 = 44;
+    const SATP_ASID_MASK: u64 = 0xffff << SATP_ASID_SHIFT;
+    let satp = SATP.get();
+    SATP.set(satp | SATP_ASID_MASK);
+    let asid = (SATP.get() & SATP_ASID_MASK) >> SATP_ASID_SHIFT;
+    SATP.set(satp);
+    asid.count_ones() as usize
+  }
 }
\ No newline at end of file
@@ -21,6 +21,7 @@ pub fn init_per_core() {
   DAIF.write(DAIF::I::Masked);
   crate::driver::INTERRUPT_CONTROLLER.init();
   crate::driver::INTERRUPT_CONTROLLER.enable(INT_TIMER);
+  crate::driver::ipi::init();
   crate::driver::timer::init();
   let pmcr = 1u64;
   let pmcntenset = 1u64 << 32;
@@ -8,6 +8,8 @@ use crate::lib::traits::ArchTrait;
 pub const BOARD_CORE_NUMBER: usize = 1;
 
 pub const BOARD_NORMAL_MEMORY_RANGE: Range<usize> = 0x8000_0000..0x8060_0000;
+// normal memory is also reached uncached this much lower
+pub const BOARD_UNCACHED_MEMORY_OFFSET: usize = 0x4000_0000;
 #[allow(dead_code)]
 pub const BOARD_DEVICE_MEMORY_RANGE: Range<usize> = 0x0000_0000..0x8000_0000;
 
@@ -19,6 +21,7 @@ pub fn init_per_core() {
   crate::driver::timer::init();
   crate::arch::Arch::exception_init();
   crate::driver::INTERRUPT_CONTROLLER.init();
+  crate::driver::ipi::init();
 }
 
 
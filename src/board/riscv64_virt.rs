@@ -21,6 +21,7 @@ pub fn init_per_core() {
   crate::driver::timer::init();
   crate::arch::Arch::exception_init();
   crate::driver::INTERRUPT_CONTROLLER.init();
+  crate::driver::ipi::init();
 }
 
 pub fn launch_other_cores() {
@@ -178,6 +178,10 @@ impl GicDistributor {
     self.IPRIORITYR[idx].set((prev & (!mask)) | (((priority as u32) << offset) & mask));
   }
 
+  fn send_sgi(&self, int: usize, targets: u8) {
+    self.SGIR.set(((targets as u32) << 16) | (int as u32 & 0b1111));
+  }
+
   fn set_config(&self, int: usize, edge: bool) {
     let idx = (int * 2) / 32;
     let offset = (int * 2) % 32;
@@ -238,6 +242,13 @@ impl InterruptController for Gic {
 }
 
 pub const INT_TIMER: Interrupt = 27; // virtual timer
+pub const INT_IPI: Interrupt = 1; // software generated
+
+// raise software generated interrupt `int` on `core_id`
+pub fn send_sgi(int: Interrupt, core_id: usize) {
+  let gicd = &GICD;
+  gicd.send_sgi(int, (1 << core_id) as u8);
+}
 
 pub static INTERRUPT_CONTROLLER: Gic = Gic {};
 
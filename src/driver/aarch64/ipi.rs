@@ -0,0 +1,12 @@
+use crate::lib::interrupt::InterruptController;
+
+use super::gic::{INT_IPI, INTERRUPT_CONTROLLER};
+
+// interrupt `core_id`, it runs `crate::mm::tlb::interrupt`
+pub fn send(core_id: usize) {
+  super::gic::send_sgi(INT_IPI, core_id);
+}
+
+pub fn init() {
+  INTERRUPT_CONTROLLER.enable(INT_IPI);
+}
@@ -9,6 +9,7 @@ pub mod uart;
 
 
 pub mod gic;
+pub mod ipi;
 mod smc;
 pub mod psci;
 pub mod timer;
@@ -1,15 +1,21 @@
 use aarch64_cpu::registers::{CNTFRQ_EL0, CNTPCT_EL0, CNTV_CTL_EL0, CNTV_TVAL_EL0};
 use tock_registers::interfaces::{Readable, Writeable};
 
-const TIMER_TICK_MS: u64 = 100;
+pub const TIMER_DEFAULT_TIME_SLICE_US: usize = 100000;
 
-pub fn next() {
-  let freq = CNTFRQ_EL0.get();
-  let count = TIMER_TICK_MS * freq / 1000;
-  CNTV_TVAL_EL0.set(count);
+// arm the timer to fire when counter reaches `deadline`
+// Note: `CNTV_TVAL_EL0` is a signed 32-bit down counter, a farther deadline fires early and is armed again
+pub fn next_at(deadline: usize) {
+  let count = deadline.saturating_sub(counter()).clamp(1, i32::MAX as usize);
+  CNTV_TVAL_EL0.set(count as u64);
   CNTV_CTL_EL0.write(CNTV_CTL_EL0::ENABLE.val(1) + CNTV_CTL_EL0::IMASK.val(0));
 }
 
+// disarm the timer until next `next_at`
+pub fn stop() {
+  CNTV_CTL_EL0.write(CNTV_CTL_EL0::ENABLE.val(0) + CNTV_CTL_EL0::IMASK.val(1));
+}
+
 pub fn frequency() -> usize {
   CNTFRQ_EL0.get() as usize
 }
@@ -19,5 +25,5 @@ pub fn counter() -> usize {
 }
 
 pub fn init() {
-  next();
+  next_at(counter() + crate::lib::timer::time_slice_count());
 }
@@ -0,0 +1,23 @@
+use super::sbi::{sbi_call, SBI_EID_IPI, SBI_FID_SEND_IPI};
+
+// SSIP of sip and SSIE of sie
+const SUPERVISOR_SOFTWARE: usize = 1 << 1;
+
+// interrupt `core_id`, it runs `crate::mm::tlb::interrupt`
+// Note: hart id and core id are the same, see `Riscv64Arch::core_id`
+pub fn send(core_id: usize) {
+  let _ = sbi_call(SBI_EID_IPI, SBI_FID_SEND_IPI, 1 << core_id, 0, 0);
+}
+
+// acknowledge the software interrupt of current core
+pub fn clear() {
+  unsafe {
+    core::arch::asm!("csrc sip, {}", in(reg) SUPERVISOR_SOFTWARE);
+  }
+}
+
+pub fn init() {
+  unsafe {
+    core::arch::asm!("csrs sie, {}", in(reg) SUPERVISOR_SOFTWARE);
+  }
+}
@@ -11,5 +11,6 @@ pub mod uart;
 pub mod plic;
 mod sbi;
 pub mod hsm;
+pub mod ipi;
 pub mod timer;
 
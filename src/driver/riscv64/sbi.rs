@@ -1,6 +1,9 @@
 pub const SBI_EID_TIMER: u32 = 0x54494D45;
 pub const SBI_FID_SET_TIMER: u32 = 0x00;
 
+pub const SBI_EID_IPI: u32 = 0x735049;
+pub const SBI_FID_SEND_IPI: u32 = 0x00;
+
 pub const SBI_EID_HSM: u32 = 0x48534D;
 pub const SBI_FID_HART_START: u32 = 0x00;
 //pub const SBI_FID_HART_STOP: u32 = 0x01;
@@ -3,10 +3,16 @@ use tock_registers::interfaces::{Readable, Writeable};
 
 use super::sbi::{sbi_call, SBI_EID_TIMER, SBI_FID_SET_TIMER};
 
-const TIMER_DEFAULT_COUNT: usize = 250000;
+pub const TIMER_DEFAULT_TIME_SLICE_US: usize = 25000;
 
-pub fn next() {
-  let _ = sbi_call(SBI_EID_TIMER, SBI_FID_SET_TIMER, counter() + TIMER_DEFAULT_COUNT, 0, 0);
+// arm the timer to fire when counter reaches `deadline`
+pub fn next_at(deadline: usize) {
+  let _ = sbi_call(SBI_EID_TIMER, SBI_FID_SET_TIMER, deadline, 0, 0);
+}
+
+// disarm the timer until next `next_at`
+pub fn stop() {
+  let _ = sbi_call(SBI_EID_TIMER, SBI_FID_SET_TIMER, usize::MAX, 0, 0);
 }
 
 // NOTE: timer frequency can be obtained from FDT
@@ -29,6 +35,6 @@ pub fn counter() -> usize {
 }
 
 pub fn init() {
-  next();
+  next_at(counter() + crate::lib::timer::time_slice_count());
   SIE.write(SIE::STIE::SET);
 }
@@ -1,15 +1,18 @@
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
-use core::sync::atomic::{AtomicU16, Ordering};
+use core::sync::atomic::{AtomicU16, AtomicUsize, Ordering};
 
 use rpabi::{CONFIG_ELF_IMAGE, PAGE_SIZE};
-use rpabi::syscall::error::{ERROR_OOM, ERROR_OOR};
-use spin::Mutex;
+use rpabi::syscall::error::{ERROR_DENIED, ERROR_MEM_NOT_MAP, ERROR_OOM, ERROR_OOR};
+use spin::{Mutex, Once};
 
-use crate::arch::PageTable;
-use crate::lib::traits::Address;
+use crate::arch::{AddressSpaceId, PageTable};
+use crate::lib::capability::CapTable;
+use crate::lib::traits::{Address, ArchTrait};
+use crate::lib::vma::VmaList;
+use crate::mm::Frame;
 use crate::mm::page_table::{EntryAttribute, PageTableEntryAttrTrait, PageTableTrait};
-use crate::util::round_up;
+use crate::util::{round_down, round_up};
 
 pub type Asid = u16;
 pub type Error = usize;
@@ -19,11 +22,20 @@ struct Inner {
   asid: Asid,
   page_table: PageTable,
   exception_handler: Mutex<Option<usize>>,
+  cycles: AtomicUsize,
+  caps: Mutex<CapTable>,
+  vmas: Mutex<VmaList>,
+  // pages of memory the address space may have mapped, see `Resident::memory`
+  limit: AtomicUsize,
+  // hardware ASID in the low bits, generation it was handed out in above them
+  hardware_asid: AtomicUsize,
 }
 
 impl Drop for Inner {
   fn drop(&mut self) {
     trace!("Drop AS{}", self.asid);
+    // Note: cores keep a reference to the address space they run, so the id is free once none of them does
+    FREE_ASIDS.lock().push_back(self.asid);
   }
 }
 
@@ -53,22 +65,121 @@ impl AddressSpace {
     let mut lock = self.0.exception_handler.lock();
     *lock = handler;
   }
+
+  pub fn cycles(&self) -> usize {
+    self.0.cycles.load(Ordering::Relaxed)
+  }
+
+  pub fn account_cycles(&self, cycles: usize) {
+    self.0.cycles.fetch_add(cycles, Ordering::Relaxed);
+  }
+
+  pub fn map_caps<F, T>(&self, f: F) -> T where F: FnOnce(&mut CapTable) -> T {
+    let mut caps = self.0.caps.lock();
+    f(&mut *caps)
+  }
+
+  pub fn limit(&self) -> usize {
+    self.0.limit.load(Ordering::Relaxed)
+  }
+
+  pub fn set_limit(&self, pages: usize) {
+    self.0.limit.store(pages, Ordering::Relaxed);
+  }
+
+  // hardware ASID tagging the TLB entries of the address space and its generation,
+  // a new one is handed out if the one it had belongs to an older generation
+  pub fn hardware_asid(&self) -> (AddressSpaceId, usize) {
+    let mut allocator = HARDWARE_ASID_ALLOCATOR.lock();
+    let mut tagged = self.0.hardware_asid.load(Ordering::Relaxed);
+    if tagged >> HARDWARE_ASID_SHIFT != allocator.generation {
+      let bits = *HARDWARE_ASID_BITS.call_once(crate::arch::Arch::asid_bits);
+      if allocator.next >= 1 << bits {
+        allocator.generation += 1;
+        allocator.next = 1;
+      }
+      tagged = (allocator.generation << HARDWARE_ASID_SHIFT) | allocator.next;
+      allocator.next += 1;
+      self.0.hardware_asid.store(tagged, Ordering::Relaxed);
+    }
+    ((tagged & ((1 << HARDWARE_ASID_SHIFT) - 1)) as AddressSpaceId, allocator.generation)
+  }
+
+  // check `pages` more pages of memory fit in the limit
+  // Note: not reserved, threads allocating at the same time may overshoot by a few pages
+  pub fn charge(&self, pages: usize) -> Result<(), Error> {
+    let resident = self.page_table().resident().memory();
+    if resident.saturating_add(pages) > self.limit() {
+      Err(ERROR_OOM)
+    } else {
+      Ok(())
+    }
+  }
+
+  // Note: page faults on reserved areas are resolved under this lock, hold it while changing their pages
+  pub fn map_vmas<F, T>(&self, f: F) -> T where F: FnOnce(&mut VmaList) -> T {
+    let mut vmas = self.0.vmas.lock();
+    f(&mut *vmas)
+  }
+
+  // copy user readable memory at `va` into `buf` through the page table
+  pub fn read_user(&self, va: usize, buf: &mut [u8]) -> Result<(), Error> {
+    let mut done = 0;
+    while done < buf.len() {
+      let src = va.checked_add(done).ok_or(ERROR_MEM_NOT_MAP)?;
+      let n = (buf.len() - done).min(PAGE_SIZE - src % PAGE_SIZE);
+      let entry = crate::mm::page_fault::populate(self, round_down(src, PAGE_SIZE)).ok_or(ERROR_MEM_NOT_MAP)?;
+      if !entry.attribute().u_readable() {
+        return Err(ERROR_DENIED);
+      }
+      let kva = (entry.pa() + src % PAGE_SIZE).pa2kva();
+      unsafe {
+        core::ptr::copy_nonoverlapping(kva as *const u8, buf[done..].as_mut_ptr(), n);
+      }
+      done += n;
+    }
+    Ok(())
+  }
 }
 
 static ASID_ALLOCATOR: AtomicU16 = AtomicU16::new(1);
 
+// ids of dropped address spaces, reused oldest first
+static FREE_ASIDS: Mutex<VecDeque<Asid>> = Mutex::new(VecDeque::new());
+
+// 0 when every id is taken
 fn new_asid() -> Asid {
-  ASID_ALLOCATOR.fetch_add(1, Ordering::Relaxed)
+  if let Some(id) = FREE_ASIDS.lock().pop_front() {
+    return id;
+  }
+  ASID_ALLOCATOR.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |id| id.checked_add(1)).unwrap_or(0)
+}
+
+// hardware ASIDs are handed out in generations, when they run out a new generation starts and
+// each core flushes its TLB before it installs a page table tagged in the new one
+// Note: hardware ASID 0 is never handed out, it tags the boot page table
+struct HardwareAsidAllocator {
+  generation: usize,
+  next: usize,
 }
 
+const HARDWARE_ASID_SHIFT: usize = 16;
+
+static HARDWARE_ASID_ALLOCATOR: Mutex<HardwareAsidAllocator> = Mutex::new(HardwareAsidAllocator {
+  generation: 1,
+  next: 1,
+});
+
+static HARDWARE_ASID_BITS: Once<usize> = Once::new();
+
 static ADDRESS_SPACE_MAP: Mutex<BTreeMap<Asid, AddressSpace>> = Mutex::new(BTreeMap::new());
 
 pub fn address_space_alloc() -> Result<AddressSpace, Error> {
+  let frame = crate::mm::page_pool::page_alloc().map_err(|_| ERROR_OOM)?;
   let id = new_asid();
   if id == 0 {
     return Err(ERROR_OOR);
   }
-  let frame = crate::mm::page_pool::page_alloc().map_err(|_| ERROR_OOM)?;
   frame.zero();
   let page_table = PageTable::new(frame);
   page_table.recursive_map(rpabi::CONFIG_RECURSIVE_PAGE_TABLE_BTM);
@@ -76,6 +187,11 @@ pub fn address_space_alloc() -> Result<AddressSpace, Error> {
     asid: id,
     page_table,
     exception_handler: Mutex::new(None),
+    cycles: AtomicUsize::new(0),
+    caps: Mutex::new(CapTable::new()),
+    vmas: Mutex::new(VmaList::new()),
+    limit: AtomicUsize::new(usize::MAX),
+    hardware_asid: AtomicUsize::new(0),
   }).map_err(|_| ERROR_OOM)?);
   let mut map = ADDRESS_SPACE_MAP.lock();
   map.insert(id, a.clone());
@@ -90,6 +206,42 @@ pub fn address_space_lookup(asid: Asid) -> Option<AddressSpace> {
   }
 }
 
+// duplicate the user pages of `src`, writable private pages become copy-on-write in both
+// Note: shared and device pages stay shared, capabilities are not inherited
+pub fn address_space_fork(src: &AddressSpace) -> Result<AddressSpace, Error> {
+  let dst = address_space_alloc()?;
+  dst.set_exception_handler(src.exception_handler());
+  dst.set_limit(src.limit());
+  // Note: holding the areas of `src` keeps its faults from changing pages while they are copied
+  let result = src.map_vmas(|vmas| {
+    dst.map_vmas(|v| *v = vmas.clone());
+    src.page_table().user_page_list().into_iter().try_for_each(|(va, frame)| {
+      let attr = match src.page_table().lookup_page(va) {
+        Some(entry) => entry.attribute(),
+        None => return Ok(()),
+      };
+      let private = matches!(frame, Frame::PhysicalMemory(_)) && !attr.u_shared();
+      let attr = if private && (attr.writable() || attr.copy_on_write()) {
+        let cow = EntryAttribute::new(false, attr.u_readable(), attr.device(), attr.k_executable(),
+                                      attr.u_executable(), true, false);
+        src.page_table().insert_page(va, frame.clone(), cow)?;
+        cow
+      } else {
+        attr
+      };
+      dst.page_table().insert_page(va, frame, attr)
+    })
+  });
+  match result {
+    Ok(_) => Ok(dst),
+    Err(_) => {
+      // Note: pages already turned copy-on-write in `src` are resolved by their next write fault
+      address_space_destroy(dst);
+      Err(ERROR_OOM)
+    }
+  }
+}
+
 pub fn address_space_destroy(a: AddressSpace) {
   trace!("Destroy AS{}", a.asid());
   let mut map = ADDRESS_SPACE_MAP.lock();
@@ -0,0 +1,163 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use rpabi::cap::*;
+
+use crate::lib::notification::Notification;
+use crate::lib::thread::Tid;
+
+pub type CapSlot = usize;
+
+// Note: messages sent through an endpoint are received by the thread which created it
+#[derive(Debug)]
+pub struct Endpoint {
+  receiver: Tid,
+}
+
+#[derive(Debug, Clone)]
+pub enum CapObject {
+  Endpoint(Arc<Endpoint>),
+  Notification(Notification),
+}
+
+#[derive(Debug)]
+struct CapNode {
+  object: CapObject,
+  rights: usize,
+  // identifies the holder to the receiver, 0 if unbadged
+  badge: usize,
+  // parent and its epoch when this capability was derived
+  parent: Option<(Capability, usize)>,
+  epoch: AtomicUsize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Capability(Arc<CapNode>);
+
+impl Capability {
+  fn new(object: CapObject) -> Self {
+    Capability(Arc::new(CapNode {
+      object,
+      rights: CAP_RIGHT_ALL,
+      badge: 0,
+      parent: None,
+      epoch: AtomicUsize::new(0),
+    }))
+  }
+
+  pub fn new_endpoint(receiver: Tid) -> Self {
+    Self::new(CapObject::Endpoint(Arc::new(Endpoint { receiver })))
+  }
+
+  pub fn new_notification() -> Self {
+    Self::new(CapObject::Notification(Notification::new()))
+  }
+
+  // receiving thread if this is an endpoint capability
+  pub fn receiver(&self) -> Option<Tid> {
+    match &self.0.object {
+      CapObject::Endpoint(e) => Some(e.receiver),
+      _ => None,
+    }
+  }
+
+  pub fn notification(&self) -> Option<Notification> {
+    match &self.0.object {
+      CapObject::Notification(n) => Some(n.clone()),
+      _ => None,
+    }
+  }
+
+  pub fn badge(&self) -> usize {
+    self.0.badge
+  }
+
+  pub fn has(&self, rights: usize) -> bool {
+    self.0.rights & rights == rights
+  }
+
+  pub fn same_object(&self, other: &Capability) -> bool {
+    match (&self.0.object, &other.0.object) {
+      (CapObject::Endpoint(a), CapObject::Endpoint(b)) => Arc::ptr_eq(a, b),
+      (CapObject::Notification(a), CapObject::Notification(b)) => a.ptr_eq(b),
+      _ => false,
+    }
+  }
+
+  // a capability is valid as long as none of its ancestors revoked it
+  pub fn valid(&self) -> bool {
+    let mut node = &self.0;
+    while let Some((parent, epoch)) = &node.parent {
+      if parent.0.epoch.load(Ordering::Relaxed) != *epoch {
+        return false;
+      }
+      node = &parent.0;
+    }
+    true
+  }
+
+  // derive a child with a subset of rights, revoked together with `self`
+  pub fn derive(&self, rights: usize) -> Capability {
+    self.mint(rights, self.0.badge)
+  }
+
+  // derive a child carrying `badge`
+  // Note: callers must not re-badge a badged capability, that would forge the identity of its holder
+  pub fn mint(&self, rights: usize, badge: usize) -> Capability {
+    Capability(Arc::new(CapNode {
+      object: self.0.object.clone(),
+      rights: self.0.rights & rights,
+      badge,
+      parent: Some((self.clone(), self.0.epoch.load(Ordering::Relaxed))),
+      epoch: AtomicUsize::new(0),
+    }))
+  }
+
+  // invalidate every capability derived from `self`, `self` stays valid
+  pub fn revoke(&self) {
+    self.0.epoch.fetch_add(1, Ordering::Relaxed);
+  }
+}
+
+// per address space, slot 0 is never used
+#[derive(Debug)]
+pub struct CapTable {
+  next: CapSlot,
+  caps: BTreeMap<CapSlot, Capability>,
+}
+
+impl CapTable {
+  pub const fn new() -> Self {
+    CapTable {
+      next: 1,
+      caps: BTreeMap::new(),
+    }
+  }
+
+  pub fn insert(&mut self, cap: Capability) -> CapSlot {
+    let slot = self.next;
+    self.next += 1;
+    self.caps.insert(slot, cap);
+    slot
+  }
+
+  // Note: revoked capabilities are dropped from the table on lookup
+  pub fn get(&mut self, slot: CapSlot) -> Option<Capability> {
+    match self.caps.get(&slot) {
+      Some(cap) if cap.valid() => Some(cap.clone()),
+      Some(_) => {
+        self.caps.remove(&slot);
+        None
+      }
+      None => None,
+    }
+  }
+
+  // slot of a valid capability to the object of `cap` with at least `rights` and the same badge
+  pub fn find(&self, cap: &Capability, rights: usize, badge: usize) -> Option<CapSlot> {
+    self.caps.iter()
+      .find(|(_, c)| c.same_object(cap) && c.has(rights) && c.badge() == badge && c.valid())
+      .map(|(slot, _)| *slot)
+  }
+}
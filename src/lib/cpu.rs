@@ -1,9 +1,12 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use spin::Once;
 
-use crate::arch::{AddressSpaceId, ContextFrame, PAGE_SIZE};
+use crate::arch::{ContextFrame, PAGE_SIZE};
 use crate::board::BOARD_CORE_NUMBER;
 use crate::lib::address_space::AddressSpace;
-use crate::lib::scheduler::scheduler;
+use crate::lib::realtime::RealtimeClass;
+use crate::lib::scheduler::RunQueue;
 use crate::lib::thread::Thread;
 use crate::lib::traits::*;
 use crate::mm::page_table::PageTableTrait;
@@ -16,9 +19,22 @@ pub struct Core {
   idle_thread: Once<Thread>,
   idle_stack: Once<PhysicalFrame>,
   address_space: Option<AddressSpace>,
+  // hardware ASID generation the TLB was last flushed for
+  asid_generation: usize,
+  run_queue: RunQueue,
+  realtime: RealtimeClass,
+  idle: AtomicBool,
+  // asked by another core to schedule again, see `reschedule_core`
+  need_schedule: AtomicBool,
+  tick: usize,
+  // cycle counter when the core last returned to the running thread
+  user_start: usize,
+  // timer counter when running thread was switched in or last charged
+  charge_start: usize,
 }
 
-// Note: only the core itself can be allowed to access its `Core`
+// Note: only the core itself can be allowed to access its `Core`,
+//       except `run_queue`, `realtime`, `idle` and `need_schedule` which are shared with other cores
 unsafe impl core::marker::Send for Core {}
 
 unsafe impl core::marker::Sync for Core {}
@@ -29,6 +45,14 @@ const CORE: Core = Core {
   idle_thread: Once::new(),
   idle_stack: Once::new(),
   address_space: None,
+  asid_generation: 0,
+  run_queue: RunQueue::new(),
+  realtime: RealtimeClass::new(),
+  idle: AtomicBool::new(false),
+  need_schedule: AtomicBool::new(false),
+  tick: 0,
+  user_start: 0,
+  charge_start: 0,
 };
 
 static mut CORES: [Core; BOARD_CORE_NUMBER] = [CORE; BOARD_CORE_NUMBER];
@@ -44,12 +68,20 @@ impl Core {
     unsafe { self.context.unwrap().as_mut() }.unwrap()
   }
 
+  // Note: set on exception entry and cleared on return, the kernel is entered from user or idle in between
+  // Note: threads are charged the cycles between return and next entry, kernel time is not counted
   pub fn set_context(&mut self, ctx: *mut ContextFrame) {
     self.context = Some(ctx);
+    if let Some(t) = &self.running_thread {
+      t.account_cycles(crate::lib::timer::current_cycle().wrapping_sub(self.user_start));
+    }
+    crate::mm::tlb::enter();
   }
 
   pub fn clear_context(&mut self) {
     self.context = None;
+    self.user_start = crate::lib::timer::current_cycle();
+    crate::mm::tlb::exit();
   }
 
   // thread
@@ -77,11 +109,84 @@ impl Core {
     }
   }
 
+  // run queue
+
+  pub fn run_queue(&self) -> &RunQueue {
+    &self.run_queue
+  }
+
+  pub fn realtime(&self) -> &RealtimeClass {
+    &self.realtime
+  }
+
+  pub fn is_idle(&self) -> bool {
+    self.idle.load(Ordering::SeqCst)
+  }
+
+  // schedule again after an interrupt if asked to, or if idle as the tick is stopped and work may have been queued
+  pub fn reschedule(&mut self) {
+    if self.need_schedule.swap(false, Ordering::SeqCst) || self.is_idle() {
+      self.schedule();
+      crate::lib::timer::program();
+    }
+  }
+
+  pub fn tick(&mut self) -> usize {
+    self.tick += 1;
+    self.tick
+  }
+
+  // charge running real-time thread for the time since it was switched in or last charged
+  pub fn charge_realtime(&mut self) {
+    let now = crate::driver::timer::counter();
+    if let Some(t) = self.running_thread().filter(|t| t.is_realtime()) {
+      crate::lib::realtime::charge(&t, now.wrapping_sub(self.charge_start));
+    }
+    self.charge_start = now;
+  }
+
+  // budget exhaustion of running thread or next job release on this core
+  pub fn realtime_next_event(&self) -> Option<usize> {
+    let exhaustion = self.running_thread()
+      .and_then(|t| t.reservation())
+      .filter(|r| !r.throttled())
+      .map(|r| self.charge_start + r.remaining());
+    match (exhaustion, self.realtime.next_release()) {
+      (Some(a), Some(b)) => Some(a.min(b)),
+      (a, b) => a.or(b),
+    }
+  }
+
   pub fn schedule(&mut self) {
-    if let Some(t) = scheduler().pop() {
-      self.run(t);
-    } else {
-      self.run(self.idle_thread());
+    let core_id = crate::arch::Arch::core_id();
+    // Note: real-time threads always run before any priority band
+    if let Some(t) = self.realtime.pick(self.running_thread().map(|t| t.tid())) {
+      if self.running_thread().map_or(true, |prev| prev.tid() != t.tid()) {
+        self.run(t);
+      }
+      return;
+    }
+    match self.running_thread().filter(|t| t.runnable() && t.allowed_on(core_id) && !t.is_realtime()) {
+      Some(prev) => {
+        // Note: a runnable thread keeps the core unless a thread of same or higher priority is waiting
+        if let Some(t) = self.run_queue.pop_at_least(prev.priority()) {
+          self.run(t);
+        }
+      }
+      None => {
+        if let Some(t) = self.run_queue.pop().or_else(|| crate::lib::scheduler::steal()) {
+          self.run(t);
+          return;
+        }
+        // Note: flag idle before a last look at the queue, a core queueing work afterwards sees it and interrupts
+        self.idle.store(true, Ordering::SeqCst);
+        core::sync::atomic::fence(Ordering::SeqCst);
+        if let Some(t) = self.run_queue.pop() {
+          self.run(t);
+        } else {
+          self.run(self.idle_thread());
+        }
+      }
     }
   }
 
@@ -94,9 +199,16 @@ impl Core {
       // info!("switch thread from {} to {}", prev.tid(), t.tid());
       // Note: normal switch
       prev.set_context(*self.context());
-      // add back to scheduler queue
-      if prev.runnable() {
-        scheduler().add(prev.clone());
+      if prev.is_realtime() {
+        crate::lib::realtime::charge(&prev, crate::driver::timer::counter().wrapping_sub(self.charge_start));
+      }
+      // add back to run queue (of this core unless affinity forbids)
+      prev.switch_out();
+      // Note: a real-time thread admitted to another core while running here can be picked there from now on
+      if let Some(r) = prev.reservation() {
+        if r.core() != crate::arch::Arch::core_id() {
+          reschedule_core(r.core());
+        }
       }
       *self.context_mut() = t.context();
     } else {
@@ -108,10 +220,25 @@ impl Core {
         // `main` prepare the context to stack
       }
     }
+    let idle_tid = self.idle_thread.get().map(|idle| idle.tid());
+    let idle = idle_tid == Some(t.tid());
+    let was_idle = self.running_thread().map_or(false, |prev| Some(prev.tid()) == idle_tid);
+    self.idle.store(idle, Ordering::SeqCst);
+    t.set_last_core(crate::arch::Arch::core_id());
+    // Note: a thread being switched in is no longer in a timed wait, its stale timer entry is ignored
+    t.set_timeout(usize::MAX);
+    t.account_switch();
+    t.set_running(true);
+    self.user_start = crate::lib::timer::current_cycle();
+    self.charge_start = crate::driver::timer::counter();
     self.set_running_thread(Some(t.clone()));
     if let Some(a) = t.address_space() {
       self.set_address_space(a);
     }
+    // Note: the slice timer is stopped while idle and must run again once there is work
+    if was_idle != idle {
+      crate::lib::timer::program();
+    }
   }
 
   pub fn address_space(&self) -> Option<AddressSpace> {
@@ -126,7 +253,14 @@ impl Core {
       // info!("switch as from {} to {}", prev.asid(), a.asid());
     }
     self.address_space = Some(a.clone());
-    crate::arch::PageTable::install_user_page_table(a.page_table().base_pa(), a.asid() as AddressSpaceId);
+    let (asid, generation) = a.hardware_asid();
+    crate::arch::PageTable::install_user_page_table(a.page_table().base_pa(), asid);
+    crate::mm::tlb::activate(a.page_table(), asid);
+    // Note: entries tagged in an older generation may carry the same hardware ASID
+    if generation != self.asid_generation {
+      crate::arch::Arch::invalidate_tlb();
+      self.asid_generation = generation;
+    }
   }
 }
 
@@ -135,6 +269,16 @@ pub fn cpu() -> &'static mut Core {
   unsafe { &mut CORES[core_id] }
 }
 
+pub fn cpu_of(core_id: usize) -> &'static Core {
+  unsafe { &CORES[core_id] }
+}
+
+// interrupt `core_id` to schedule again
+pub fn reschedule_core(core_id: usize) {
+  cpu_of(core_id).need_schedule.store(true, Ordering::SeqCst);
+  crate::driver::ipi::send(core_id);
+}
+
 #[no_mangle]
 fn idle_thread(_arg: usize) {
   loop {
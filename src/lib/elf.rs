@@ -9,6 +9,8 @@ use crate::util::round_up;
 
 pub type Error = usize;
 
+const BLOCK_SIZE: usize = 1 << rpabi::PAGE_TABLE_L2_SHIFT;
+
 pub fn load(src: &'static [u8], page_table: &PageTable) -> Result<usize, Error> {
   if let Ok(elf) = ElfFile::new(src) {
     let entry_point = elf.header.pt2.entry_point() as usize;
@@ -53,8 +55,17 @@ pub fn load(src: &'static [u8], page_table: &PageTable) -> Result<usize, Error>
         }
       };
 
-      for i in 0..file_page_num {
-        page_table.map(va + i * PAGE_SIZE, pa + i * PAGE_SIZE, attr)?;
+      let mut i = 0;
+      while i < file_page_num {
+        let (page_va, page_pa) = (va + i * PAGE_SIZE, pa + i * PAGE_SIZE);
+        // Note: 2 MB aligned runs of the image are mapped as blocks
+        if page_va % BLOCK_SIZE == 0 && page_pa % BLOCK_SIZE == 0 && (file_page_num - i) * PAGE_SIZE >= BLOCK_SIZE {
+          page_table.map_block(page_va, page_pa, BLOCK_SIZE, attr)?;
+          i += BLOCK_SIZE / PAGE_SIZE;
+        } else {
+          page_table.map(page_va, page_pa, attr)?;
+          i += 1;
+        }
       }
       for i in file_page_num..mem_page_num {
         let frame = crate::mm::page_pool::page_alloc().map_err(|_| ERROR_OOM)?;
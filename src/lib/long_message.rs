@@ -0,0 +1,224 @@
+use rpabi::itc::*;
+use rpabi::syscall::error::*;
+
+use crate::arch::PAGE_SIZE;
+use crate::lib::address_space::AddressSpace;
+use crate::lib::thread::{ItcMessage, Thread};
+use crate::lib::traits::*;
+use crate::mm::page_table::{EntryAttribute, PageTableEntryAttrTrait, PageTableTrait};
+use crate::util::{round_down, round_up};
+
+// buffer of a caller passed along with its call
+#[derive(Debug, Copy, Clone)]
+pub struct LongMessage {
+  va: usize,
+  len: usize,
+  mode: usize,
+  // where it landed in the receiver, set once copied or granted
+  delivered: Option<usize>,
+}
+
+impl LongMessage {
+  fn grant(&self) -> bool {
+    self.mode & ITC_LONG_GRANT != 0
+  }
+
+  fn writable(&self) -> bool {
+    self.mode & ITC_LONG_WRITE != 0
+  }
+
+  // pages spanned by the buffer
+  fn pages(&self) -> usize {
+    (round_up(self.va + self.len, PAGE_SIZE) - round_down(self.va, PAGE_SIZE)) / PAGE_SIZE
+  }
+
+  // start and room the message takes in the receive buffer of the receiver
+  fn target(&self, r: &ReceiveBuffer) -> Option<usize> {
+    let (va, len) = if self.grant() { r.window } else { r.copy };
+    let footprint = if self.grant() { self.pages() * PAGE_SIZE } else { self.len };
+    if footprint <= len { Some(va) } else { None }
+  }
+}
+
+// where long messages land in a receiver, copies go to `copy` and grants are mapped at `window`
+#[derive(Debug, Copy, Clone)]
+pub struct ReceiveBuffer {
+  copy: (usize, usize),
+  window: (usize, usize),
+}
+
+// `copy` must be writable memory of `a`, `window` a page aligned range with nothing mapped,
+// either may be empty
+pub fn receive_buffer(a: &AddressSpace, copy: (usize, usize), window: (usize, usize)) -> Result<ReceiveBuffer, usize> {
+  let (copy_va, copy_len) = copy;
+  let (window_va, window_len) = window;
+  if copy_len > ITC_LONG_MAX || window_len > ITC_LONG_MAX + PAGE_SIZE {
+    return Err(ERROR_INVARG);
+  }
+  if copy_len != 0 {
+    validate(a, copy_va, copy_len, ITC_LONG_WRITE)?;
+  }
+  if window_va % PAGE_SIZE != 0 || window_len % PAGE_SIZE != 0 {
+    return Err(ERROR_INVARG);
+  }
+  let window_end = window_va.checked_add(window_len).ok_or(ERROR_INVARG)?;
+  let mut page = window_va;
+  while page < window_end {
+    if a.page_table().lookup_page(page).is_some() {
+      return Err(ERROR_INVARG);
+    }
+    page += PAGE_SIZE;
+  }
+  Ok(ReceiveBuffer { copy, window })
+}
+
+// check `[va, va + len)` of `a` is mapped to user with the access `mode` lets the receiver have
+pub fn validate(a: &AddressSpace, va: usize, len: usize, mode: usize) -> Result<LongMessage, usize> {
+  if len == 0 || len > ITC_LONG_MAX || mode & !(ITC_LONG_READ | ITC_LONG_WRITE | ITC_LONG_GRANT) != 0 {
+    return Err(ERROR_INVARG);
+  }
+  if mode & (ITC_LONG_READ | ITC_LONG_WRITE) == 0 {
+    return Err(ERROR_INVARG);
+  }
+  let end = va.checked_add(len).ok_or(ERROR_INVARG)?;
+  let mut page = round_down(va, PAGE_SIZE);
+  while page < end {
+    let mut entry = crate::mm::page_fault::populate(a, page).ok_or(ERROR_MEM_NOT_MAP)?;
+    // Note: the kernel writes through its own mapping, so copy-on-write sharing is broken up front
+    if mode & ITC_LONG_WRITE != 0 && entry.attribute().copy_on_write() && crate::mm::page_fault::copy_on_write(a, page) {
+      entry = a.page_table().lookup_page(page).ok_or(ERROR_MEM_NOT_MAP)?;
+    }
+    let attr = entry.attribute();
+    if !attr.u_readable() || (mode & ITC_LONG_WRITE != 0 && !attr.writable()) {
+      return Err(ERROR_DENIED);
+    }
+    // Note: only ordinary user pages may be granted, device windows stay with their owner
+    if mode & ITC_LONG_GRANT != 0 && a.page_table().lookup_user_page(page).is_none() {
+      return Err(ERROR_DENIED);
+    }
+    page += PAGE_SIZE;
+  }
+  Ok(LongMessage { va, len, mode, delivered: None })
+}
+
+// check the receive buffer of `receiver` can take `m`
+pub fn fits(receiver: &Thread, m: &LongMessage) -> bool {
+  receiver.receive_buffer().and_then(|r| m.target(&r)).is_some()
+}
+
+// resolve the page holding `va` for a write the way a write fault does, breaking copy-on-write sharing
+// Note: the mapping may have changed since the range was validated
+fn writable_page(a: &AddressSpace, va: usize) -> Result<crate::mm::page_table::Entry, usize> {
+  let page = round_down(va, PAGE_SIZE);
+  let mut entry = crate::mm::page_fault::populate(a, page).ok_or(ERROR_INVARG)?;
+  if entry.attribute().copy_on_write() && crate::mm::page_fault::copy_on_write(a, page) {
+    entry = a.page_table().lookup_page(page).ok_or(ERROR_INVARG)?;
+  }
+  let attr = entry.attribute();
+  if attr.u_readable() && attr.writable() && !attr.copy_on_write() {
+    Ok(entry)
+  } else {
+    Err(ERROR_INVARG)
+  }
+}
+
+// copy `len` bytes between address spaces
+fn copy(src: &AddressSpace, src_va: usize, dst: &AddressSpace, dst_va: usize, len: usize) -> Result<(), usize> {
+  let mut done = 0;
+  while done < len {
+    let s = src_va + done;
+    let d = dst_va + done;
+    let n = (len - done)
+      .min(PAGE_SIZE - s % PAGE_SIZE)
+      .min(PAGE_SIZE - d % PAGE_SIZE);
+    let s_entry = crate::mm::page_fault::populate(src, round_down(s, PAGE_SIZE)).ok_or(ERROR_MEM_NOT_MAP)?;
+    if !s_entry.attribute().u_readable() {
+      return Err(ERROR_INVARG);
+    }
+    let d_entry = writable_page(dst, d)?;
+    unsafe {
+      core::ptr::copy(
+        (s_entry.pa() + s % PAGE_SIZE).pa2kva() as *const u8,
+        (d_entry.pa() + d % PAGE_SIZE).pa2kva() as *mut u8,
+        n,
+      );
+    }
+    done += n;
+  }
+  Ok(())
+}
+
+// copy or grant the long message of `sender`, if any, into the receive buffer of `receiver`,
+// `c` and `d` of the delivered message become its address and length there, 0 if it did not fit
+// Note: a receiver holds one long message at a time, the next delivery reuses its receive buffer,
+// a grant replaced this way is withdrawn when the first caller is replied
+pub fn deliver(sender: &Thread, receiver: &Thread, message: ItcMessage) -> ItcMessage {
+  let (a, b, c, d) = message;
+  sender.map_long_message(|m| match m {
+    None => (a, b, c, d),
+    Some(m) => match transfer(sender, receiver, m) {
+      Some((c, d)) => (a, b, c, d),
+      None => (a, b, 0, 0),
+    },
+  })
+}
+
+fn transfer(sender: &Thread, receiver: &Thread, m: &mut LongMessage) -> Option<(usize, usize)> {
+  let buf = m.target(&receiver.receive_buffer()?)?;
+  let src = sender.address_space()?;
+  let dst = receiver.address_space()?;
+  if m.grant() {
+    let attr = if m.writable() { EntryAttribute::user_data() } else { EntryAttribute::user_readonly() };
+    let base = round_down(m.va, PAGE_SIZE);
+    // Note: marked first, so that pages mapped before a failure are withdrawn on reply as well
+    m.delivered = Some(buf);
+    for i in 0..m.pages() {
+      // Note: the receiver writes the frame itself, copy-on-write sharing the sender took on since must be broken
+      if m.writable() {
+        writable_page(&src, base + i * PAGE_SIZE).ok()?;
+      }
+      let frame = src.page_table().lookup_user_page(base + i * PAGE_SIZE)?;
+      dst.page_table().insert_page(buf + i * PAGE_SIZE, frame, attr).ok()?;
+    }
+    Some((buf + m.va % PAGE_SIZE, m.len))
+  } else {
+    if m.mode & ITC_LONG_READ != 0 {
+      copy(&src, m.va, &dst, buf, m.len).ok()?;
+    }
+    m.delivered = Some(buf);
+    Some((buf, m.len))
+  }
+}
+
+// on reply, copy a writable buffer back to `sender` or withdraw a grant from `receiver`
+pub fn complete(sender: &Thread, receiver: &Thread) {
+  // Note: the receive buffer may have been changed since, the message stays where it was delivered
+  let (m, buf) = match sender.map_long_message(|m| m.take()) {
+    Some(m) => match m.delivered {
+      Some(buf) => (m, buf),
+      None => return,
+    },
+    None => return,
+  };
+  let (src, dst) = match (receiver.address_space(), sender.address_space()) {
+    (Some(src), Some(dst)) => (src, dst),
+    _ => return,
+  };
+  if m.grant() {
+    for i in 0..m.pages() {
+      let _ = src.page_table().remove_page(buf + i * PAGE_SIZE);
+    }
+  } else if m.writable() {
+    let _ = copy(&src, buf, &dst, m.va, m.len);
+  }
+}
+
+// withdraw a grant without copying back, the sender stopped waiting for reply
+pub fn cancel(sender: &Thread, receiver: &Thread) {
+  sender.map_long_message(|m| {
+    if let Some(m) = m.as_mut() {
+      m.mode &= !ITC_LONG_WRITE;
+    }
+  });
+  complete(sender, receiver);
+}
@@ -1,7 +1,12 @@
 pub mod print;
 pub mod address_space;
+pub mod vma;
+pub mod capability;
+pub mod notification;
+pub mod long_message;
 pub mod elf;
 pub mod scheduler;
+pub mod realtime;
 pub mod syscall;
 pub mod thread;
 pub mod cpu;
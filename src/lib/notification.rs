@@ -0,0 +1,110 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::fmt::{Debug, Formatter};
+
+use rpabi::cap::{CAP_BADGE_NONE, CAP_NOTIFICATION_SENDER};
+use spin::Mutex;
+
+use crate::lib::thread::{Thread, thread_lookup, Tid};
+use crate::lib::traits::ContextFrameTrait;
+use crate::syscall::SyscallOutRegisters::{Hexad, Single};
+
+struct Inner {
+  word: usize,
+  waiters: VecDeque<Thread>,
+  // thread woken from `itc_receive` when signalled
+  bound: Option<Tid>,
+}
+
+// Note: lock order is thread status before notification
+#[derive(Clone)]
+pub struct Notification(Arc<Mutex<Inner>>);
+
+impl Debug for Notification {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    write!(f, "Notification({:x})", self.0.lock().word)
+  }
+}
+
+impl Notification {
+  pub fn new() -> Self {
+    Notification(Arc::new(Mutex::new(Inner {
+      word: 0,
+      waiters: VecDeque::new(),
+      bound: None,
+    })))
+  }
+
+  pub fn ptr_eq(&self, other: &Notification) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+
+  // take and clear pending signal bits
+  pub fn take(&self) -> usize {
+    core::mem::take(&mut self.0.lock().word)
+  }
+
+  // take pending signal bits, or queue `t` as a waiter if there is none
+  pub fn take_or_enqueue(&self, t: &Thread) -> usize {
+    let mut inner = self.0.lock();
+    if inner.word == 0 {
+      inner.waiters.push_back(t.clone());
+    }
+    core::mem::take(&mut inner.word)
+  }
+
+  pub fn remove_waiter(&self, tid: Tid) {
+    self.0.lock().waiters.retain(|t| t.tid() != tid);
+  }
+
+  pub fn bind(&self, tid: Tid) -> bool {
+    let mut inner = self.0.lock();
+    match inner.bound {
+      Some(bound) if bound != tid && thread_lookup(bound).is_some() => false,
+      _ => {
+        inner.bound = Some(tid);
+        true
+      }
+    }
+  }
+
+  pub fn signal(&self, bits: usize) {
+    let bound = {
+      let mut inner = self.0.lock();
+      inner.word |= bits;
+      inner.bound
+    };
+    // Note: a waiter no longer waiting (woken otherwise or exited) is dropped, the next one is tried
+    loop {
+      let t = match self.0.lock().waiters.pop_front() {
+        Some(t) => t,
+        None => break,
+      };
+      let mut consumed = false;
+      let woken = t.wake_from_notification(|| {
+        let word = self.take();
+        if word == 0 {
+          // Note: bits were consumed by another signal or poll meanwhile, keep waiting
+          self.0.lock().waiters.push_front(t.clone());
+          consumed = true;
+          return false;
+        }
+        t.map_with_context(|ctx| ctx.set_syscall_result(&Ok(Single(word))));
+        true
+      });
+      if woken || consumed {
+        return;
+      }
+    }
+    if let Some(t) = bound.and_then(thread_lookup) {
+      t.notify_request(|| {
+        let word = self.take();
+        if word == 0 {
+          return false;
+        }
+        t.map_with_context(|ctx| ctx.set_syscall_result(&Ok(Hexad(CAP_NOTIFICATION_SENDER, word, 0, 0, 0, CAP_BADGE_NONE))));
+        true
+      });
+    }
+  }
+}
@@ -0,0 +1,189 @@
+use alloc::vec::Vec;
+
+use rpabi::syscall::error::{ERROR_DENIED, ERROR_INVARG};
+use spin::Mutex;
+
+use crate::board::BOARD_CORE_NUMBER;
+use crate::lib::cpu::cpu_of;
+use crate::lib::thread::Thread;
+use crate::lib::traits::ArchTrait;
+use crate::syscall::event::deadline_overrun_signal;
+
+pub type Error = usize;
+
+// per-mille of a core that can be reserved by real-time threads
+const REALTIME_DENSITY_MAX: usize = 900;
+
+// per-mille of a core a thread needs to run `budget` within every `deadline`, rounded up
+// Note: with constrained deadlines EDF is schedulable if the sum of densities stays within the core
+fn density(budget: usize, deadline: usize) -> usize {
+  ((budget as u128 * 1000 + deadline as u128 - 1) / deadline as u128) as usize
+}
+
+// whether a core with `reserved` per-mille taken can admit `density` more
+fn fits(reserved: usize, density: usize) -> bool {
+  reserved + density <= REALTIME_DENSITY_MAX
+}
+
+// Note: all times are in timer counter ticks
+#[derive(Debug, Copy, Clone)]
+pub struct Reservation {
+  core: usize,
+  period: usize,
+  budget: usize,
+  deadline: usize,
+  density: usize,
+  // state of current job
+  remaining: usize,
+  absolute_deadline: usize,
+  next_release: usize,
+  throttled: bool,
+}
+
+impl Reservation {
+  pub fn core(&self) -> usize {
+    self.core
+  }
+
+  pub fn remaining(&self) -> usize {
+    self.remaining
+  }
+
+  pub fn throttled(&self) -> bool {
+    self.throttled
+  }
+}
+
+struct Inner {
+  threads: Vec<Thread>,
+  density: usize,
+}
+
+// Note: partitioned EDF, each admitted thread is pinned to one core
+pub struct RealtimeClass(Mutex<Inner>);
+
+impl RealtimeClass {
+  pub const fn new() -> Self {
+    RealtimeClass(Mutex::new(Inner {
+      threads: Vec::new(),
+      density: 0,
+    }))
+  }
+
+  // runnable thread with budget left and earliest absolute deadline
+  // Note: a thread admitted while running on another core is skipped until switched out there,
+  //       `current` is the thread running on this core
+  pub fn pick(&self, current: Option<usize>) -> Option<Thread> {
+    let inner = self.0.lock();
+    let mut earliest: Option<(usize, &Thread)> = None;
+    for t in inner.threads.iter() {
+      if !t.runnable() || (t.running() && current != Some(t.tid())) {
+        continue;
+      }
+      if let Some(r) = t.reservation() {
+        if r.throttled {
+          continue;
+        }
+        match earliest {
+          Some((d, _)) if d <= r.absolute_deadline => {}
+          _ => earliest = Some((r.absolute_deadline, t)),
+        }
+      }
+    }
+    earliest.map(|(_, t)| t.clone())
+  }
+
+  // start a new job for every thread whose release time has come
+  pub fn release(&self, now: usize) {
+    let inner = self.0.lock();
+    for t in inner.threads.iter() {
+      t.map_reservation(|r| {
+        if let Some(r) = r {
+          if now >= r.next_release {
+            let release = r.next_release + (now - r.next_release) / r.period * r.period;
+            r.remaining = r.budget;
+            r.absolute_deadline = release.saturating_add(r.deadline);
+            r.next_release = release.saturating_add(r.period);
+            r.throttled = false;
+          }
+        }
+      });
+    }
+  }
+
+  pub fn next_release(&self) -> Option<usize> {
+    let inner = self.0.lock();
+    inner.threads.iter().filter_map(|t| t.reservation().map(|r| r.next_release)).min()
+  }
+}
+
+pub fn admit(t: &Thread, period: usize, budget: usize, deadline: usize) -> Result<(), Error> {
+  if budget == 0 || budget > deadline || deadline > period {
+    return Err(ERROR_INVARG);
+  }
+  if t.is_realtime() {
+    return Err(ERROR_INVARG);
+  }
+  // Note: budget <= deadline, so density never exceeds 1000
+  let density = density(budget, deadline);
+  for core_id in (0..BOARD_CORE_NUMBER).filter(|i| t.allowed_on(*i)) {
+    let mut inner = cpu_of(core_id).realtime().0.lock();
+    if !fits(inner.density, density) {
+      continue;
+    }
+    inner.density += density;
+    let now = crate::driver::timer::counter();
+    t.map_reservation(|r| *r = Some(Reservation {
+      core: core_id,
+      period,
+      budget,
+      deadline,
+      density,
+      remaining: budget,
+      absolute_deadline: now.saturating_add(deadline),
+      next_release: now.saturating_add(period),
+      throttled: false,
+    }));
+    inner.threads.push(t.clone());
+    drop(inner);
+    // Note: also drops `t` from priority run queues, real-time threads are picked by `RealtimeClass`
+    crate::lib::thread::thread_set_affinity(t, 1 << core_id);
+    if core_id != crate::arch::Arch::core_id() {
+      crate::lib::cpu::reschedule_core(core_id);
+    }
+    return Ok(());
+  }
+  Err(ERROR_DENIED)
+}
+
+pub fn detach(t: &Thread) {
+  if let Some(r) = t.map_reservation(|r| r.take()) {
+    let mut inner = cpu_of(r.core).realtime().0.lock();
+    inner.threads.retain(|x| x.tid() != t.tid());
+    inner.density -= r.density;
+  }
+}
+
+// charge a running thread, throttle it until next release once budget is exhausted
+pub fn charge(t: &Thread, elapsed: usize) {
+  let overrun = t.map_reservation(|r| match r {
+    Some(r) if !r.throttled => {
+      r.remaining = r.remaining.saturating_sub(elapsed);
+      r.throttled = r.remaining == 0;
+      r.throttled
+    }
+    _ => false,
+  });
+  if overrun {
+    deadline_overrun_signal(t.tid());
+  }
+}
+
+// current job finished early, wait for next release
+pub fn complete(t: &Thread) {
+  t.map_reservation(|r| {
+    if let Some(r) = r {
+      r.throttled = true;
+    }
+  });
+}
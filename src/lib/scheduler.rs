@@ -1,42 +1,176 @@
 use alloc::collections::VecDeque;
+use core::sync::atomic::{AtomicUsize, fence, Ordering};
 
-use spin::{Mutex, Once};
+use rpabi::thread::THREAD_PRIORITY_NUM;
+use spin::Mutex;
 
-use crate::lib::thread::Thread;
+use crate::board::BOARD_CORE_NUMBER;
+use crate::lib::cpu::{cpu, cpu_of};
+use crate::lib::thread::{Thread, Tid};
+use crate::lib::traits::*;
 
-pub struct RoundRobinScheduler {
-  inner: Mutex<VecDeque<Thread>>,
+// rebalance run queues every `SCHEDULER_BALANCE_TICKS` timer ticks
+pub const SCHEDULER_BALANCE_TICKS: usize = 4;
+
+const EMPTY_BAND: VecDeque<Thread> = VecDeque::new();
+
+// Note: fixed priority bands, round robin within a band
+pub struct RunQueue {
+  inner: Mutex<[VecDeque<Thread>; THREAD_PRIORITY_NUM]>,
+  len: AtomicUsize,
 }
 
-impl RoundRobinScheduler {
-  fn new() -> Self {
-    RoundRobinScheduler {
-      inner: Mutex::new(VecDeque::new()),
+impl RunQueue {
+  pub const fn new() -> Self {
+    RunQueue {
+      inner: Mutex::new([EMPTY_BAND; THREAD_PRIORITY_NUM]),
+      len: AtomicUsize::new(0),
     }
   }
 
+  pub fn len(&self) -> usize {
+    self.len.load(Ordering::Relaxed)
+  }
+
   pub fn add_front(&self, thread: Thread) {
     let mut inner = self.inner.lock();
-    inner.push_front(thread);
+    inner[thread.priority()].push_front(thread);
+    self.len.fetch_add(1, Ordering::Relaxed);
   }
 
   pub fn add(&self, thread: Thread) {
     let mut inner = self.inner.lock();
-    inner.push_back(thread);
+    inner[thread.priority()].push_back(thread);
+    self.len.fetch_add(1, Ordering::Relaxed);
   }
 
   pub fn pop(&self) -> Option<Thread> {
+    self.pop_at_least(0)
+  }
+
+  // pop the first thread of the highest non-empty band not lower than `priority`
+  pub fn pop_at_least(&self, priority: usize) -> Option<Thread> {
+    let mut inner = self.inner.lock();
+    for band in inner[priority..].iter_mut().rev() {
+      if let Some(t) = band.pop_front() {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        return Some(t);
+      }
+    }
+    None
+  }
+
+  // take the thread which waits longest in the highest band that may run on `core_id`
+  pub fn steal(&self, core_id: usize) -> Option<Thread> {
     let mut inner = self.inner.lock();
-    inner.pop_front()
+    for band in inner.iter_mut().rev() {
+      if let Some(i) = band.iter().rposition(|t| t.allowed_on(core_id)) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        return band.remove(i);
+      }
+    }
+    None
+  }
+
+  pub fn remove(&self, tid: Tid) -> Option<Thread> {
+    let mut inner = self.inner.lock();
+    for band in inner.iter_mut() {
+      if let Some(i) = band.iter().position(|t| t.tid() == tid) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        return band.remove(i);
+      }
+    }
+    None
   }
 }
 
-static SCHEDULER: Once<RoundRobinScheduler> = Once::new();
+// enqueue on the core the thread last ran on (cache affinity), or on an idle core if that one is busy
+// Note: called with the status lock of `t` held
+pub fn add(t: Thread) {
+  // Note: real-time threads are picked from `RealtimeClass` of their core
+  if t.is_realtime() {
+    return;
+  }
+  // Note: still running on a core which has not switched it out yet, that core queues it, see `Thread::switch_out`
+  if t.running() {
+    return;
+  }
+  let core_id = crate::arch::Arch::core_id();
+  let mut target = t.last_core();
+  if !t.allowed_on(target) {
+    target = (0..BOARD_CORE_NUMBER).find(|i| t.allowed_on(*i)).unwrap_or(core_id);
+  }
+  if !cpu_of(target).is_idle() {
+    if let Some(i) = (0..BOARD_CORE_NUMBER).find(|i| t.allowed_on(*i) && cpu_of(*i).is_idle()) {
+      target = i;
+    }
+  }
+  cpu_of(target).run_queue().add(t);
+  // Note: pairs with the fence of an idle core taking a last look at its queue, see `Core::schedule`
+  fence(Ordering::SeqCst);
+  if target != core_id && cpu_of(target).is_idle() {
+    crate::lib::cpu::reschedule_core(target);
+  }
+}
 
-pub fn scheduler() -> &'static RoundRobinScheduler {
-  if let Some(s) = SCHEDULER.get() {
-    s
+// Note: used for IPC hand-off, the thread should run next on this core
+pub fn add_front(t: Thread) {
+  if t.running() {
+    return;
+  }
+  if t.allowed_on(crate::arch::Arch::core_id()) {
+    cpu().run_queue().add_front(t);
   } else {
-    SCHEDULER.call_once(|| RoundRobinScheduler::new())
+    add(t);
+  }
+}
+
+pub fn remove(tid: Tid) -> Option<Thread> {
+  for i in 0..BOARD_CORE_NUMBER {
+    if let Some(t) = cpu_of(i).run_queue().remove(tid) {
+      return Some(t);
+    }
+  }
+  None
+}
+
+fn busiest_core() -> usize {
+  let mut busiest = 0;
+  for i in 1..BOARD_CORE_NUMBER {
+    if cpu_of(i).run_queue().len() > cpu_of(busiest).run_queue().len() {
+      busiest = i;
+    }
+  }
+  busiest
+}
+
+// called by an idle core
+pub fn steal() -> Option<Thread> {
+  let core_id = crate::arch::Arch::core_id();
+  let busiest = busiest_core();
+  if busiest == core_id {
+    return None;
+  }
+  cpu_of(busiest).run_queue().steal(core_id)
+}
+
+// pull threads from the busiest core until both queues are about even
+pub fn balance() {
+  let core_id = crate::arch::Arch::core_id();
+  let local = cpu().run_queue();
+  let busiest = busiest_core();
+  if busiest == core_id {
+    return;
+  }
+  let remote = cpu_of(busiest).run_queue();
+  let len = remote.len();
+  if len <= local.len() + 1 {
+    return;
+  }
+  for _ in 0..(len - local.len()) / 2 {
+    match remote.steal(core_id) {
+      None => break,
+      Some(t) => local.add(t),
+    }
   }
 }
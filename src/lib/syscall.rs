@@ -27,16 +27,45 @@ static SYSCALL_NAMES: [&str; SYS_MAX] = [
   "itc_send",
   "itc_call",
   "server_register",
-  "server_tid",
+  "server_cap",
   "set_exception_handler",
   "getc",
   "yield_to",
   "reply_recv",
+  "thread_set_priority",
+  "thread_set_affinity",
+  "sleep",
+  "sleep_until",
+  "thread_stat",
+  "thread_set_realtime",
+  "endpoint_create",
+  "cap_mint",
+  "cap_copy",
+  "cap_revoke",
+  "notification_create",
+  "notification_signal",
+  "notification_wait",
+  "notification_poll",
+  "notification_bind",
+  "channel_create",
+  "itc_call_long",
+  "itc_buffer_set",
+  "service_register",
+  "service_lookup",
+  "service_unregister",
+  "address_space_fork",
+  "vma_reserve",
+  "vma_protect",
+  "vma_release",
+  "vma_pager",
+  "mem_alloc_dma",
+  "address_space_limit",
+  "address_space_stat",
 ];
 
 
 static SYSCALL_ARGC: [usize; SYS_MAX] = [
-  1, 1, 1, 0, 0, 1, 2, 3, 5, 2, 0, 4, 2, 1, 0, 5, 5, 1, 1, 1, 0, 1, 5
+  1, 1, 1, 0, 0, 1, 2, 4, 5, 2, 0, 4, 2, 1, 1, 5, 6, 2, 1, 1, 0, 1, 6, 2, 2, 1, 1, 1, 4, 0, 3, 2, 1, 0, 2, 1, 1, 1, 4, 6, 4, 3, 3, 2, 1, 4, 4, 3, 4, 4, 2, 1
 ];
 
 pub fn syscall() {
@@ -55,22 +84,51 @@ pub fn syscall() {
       SYS_THREAD_YIELD => thread::thread_yield(),
       SYS_THREAD_DESTROY => thread::thread_destroy(arg(0)),
       SYS_EVENT_WAIT => event::event_wait(arg(0), arg(1)),
-      SYS_MEM_ALLOC => mm::mem_alloc(arg(0) as u16, arg(1), arg(2)),
+      SYS_MEM_ALLOC => mm::mem_alloc(arg(0) as u16, arg(1), arg(2), arg(3)),
       SYS_MEM_MAP => mm::mem_map(arg(0) as u16, arg(1), arg(2) as u16, arg(3), arg(4)),
       SYS_MEM_UNMAP => mm::mem_unmap(arg(0) as u16, arg(1)),
       SYS_ADDRESS_SPACE_ALLOC => address_space::address_space_alloc(),
       SYS_THREAD_ALLOC => thread::thread_alloc(arg(0) as u16, arg(1), arg(2), arg(3)),
       SYS_THREAD_SET_STATUS => thread::thread_set_status(arg(0), arg(1)),
       SYS_ADDRESS_SPACE_DESTROY => address_space::address_space_destroy(arg(0) as u16),
-      SYS_ITC_RECV => ipc::itc_receive(),
+      SYS_ITC_RECV => ipc::itc_receive(arg(0)),
       SYS_ITC_SEND => ipc::itc_send(arg(0), arg(1), arg(2), arg(3), arg(4)),
-      SYS_ITC_CALL => ipc::itc_call(arg(0), arg(1), arg(2), arg(3), arg(4)),
-      SYS_SERVER_REGISTER => server::server_register(arg(0)),
-      SYS_SERVER_TID => server::server_tid(arg(0)),
+      SYS_ITC_CALL => ipc::itc_call(arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)),
+      SYS_SERVER_REGISTER => server::server_register(arg(0), arg(1)),
+      SYS_SERVER_CAP => server::server_cap(arg(0)),
       SYS_SET_EXCEPTION_HANDLER => misc::set_exception_handler(arg(0)),
       SYS_GETC => misc::getc(),
       SYS_YIELD_TO => thread::yield_to(arg(0)),
-      SYS_REPLY_RECV => ipc::itc_reply_recv(arg(0), arg(1), arg(2), arg(3), arg(4)),
+      SYS_REPLY_RECV => ipc::itc_reply_recv(arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)),
+      SYS_THREAD_SET_PRIORITY => thread::thread_set_priority(arg(0), arg(1)),
+      SYS_THREAD_SET_AFFINITY => thread::thread_set_affinity(arg(0), arg(1)),
+      SYS_SLEEP => thread::sleep(arg(0)),
+      SYS_SLEEP_UNTIL => thread::sleep_until(arg(0)),
+      SYS_THREAD_STAT => thread::thread_stat(arg(0)),
+      SYS_THREAD_SET_REALTIME => thread::thread_set_realtime(arg(0), arg(1), arg(2), arg(3)),
+      SYS_ENDPOINT_CREATE => capability::endpoint_create(),
+      SYS_CAP_MINT => capability::cap_mint(arg(0), arg(1), arg(2)),
+      SYS_CAP_COPY => capability::cap_copy(arg(0), arg(1) as u16),
+      SYS_CAP_REVOKE => capability::cap_revoke(arg(0)),
+      SYS_NOTIFICATION_CREATE => notification::notification_create(),
+      SYS_NOTIFICATION_SIGNAL => notification::notification_signal(arg(0), arg(1)),
+      SYS_NOTIFICATION_WAIT => notification::notification_wait(arg(0)),
+      SYS_NOTIFICATION_POLL => notification::notification_poll(arg(0)),
+      SYS_NOTIFICATION_BIND => notification::notification_bind(arg(0)),
+      SYS_CHANNEL_CREATE => channel::channel_create(arg(0), arg(1), arg(2) as u16, arg(3)),
+      SYS_ITC_CALL_LONG => ipc::itc_call_long(arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)),
+      SYS_ITC_BUFFER_SET => ipc::itc_buffer_set(arg(0), arg(1), arg(2), arg(3)),
+      SYS_SERVICE_REGISTER => server::service_register(arg(0), arg(1), arg(2)),
+      SYS_SERVICE_LOOKUP => server::service_lookup(arg(0), arg(1), arg(2)),
+      SYS_SERVICE_UNREGISTER => server::service_unregister(arg(0), arg(1)),
+      SYS_ADDRESS_SPACE_FORK => address_space::address_space_fork(arg(0) as u16),
+      SYS_VMA_RESERVE => mm::vma_reserve(arg(0) as u16, arg(1), arg(2), arg(3)),
+      SYS_VMA_PROTECT => mm::vma_protect(arg(0) as u16, arg(1), arg(2), arg(3)),
+      SYS_VMA_RELEASE => mm::vma_release(arg(0) as u16, arg(1), arg(2)),
+      SYS_VMA_PAGER => mm::vma_pager(arg(0) as u16, arg(1), arg(2), arg(3)),
+      SYS_MEM_ALLOC_DMA => mm::mem_alloc_dma(arg(0) as u16, arg(1), arg(2), arg(3)),
+      SYS_ADDRESS_SPACE_LIMIT => address_space::address_space_limit(arg(0) as u16, arg(1)),
+      SYS_ADDRESS_SPACE_STAT => address_space::address_space_stat(arg(0) as u16),
       _ => {
         warn!("system call: unrecognized system call number");
         Err(ERROR_INVARG)
@@ -1,16 +1,23 @@
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
-use core::sync::atomic::AtomicUsize;
-use core::sync::atomic::Ordering::Relaxed;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize};
+use core::sync::atomic::Ordering::{Relaxed, SeqCst};
 
+use rpabi::syscall::error::{ERROR_INVARG, ERROR_TIMEOUT};
+use rpabi::thread::{THREAD_AFFINITY_ANY, THREAD_PRIORITY_DEFAULT};
 use spin::Mutex;
 
 use crate::arch::ContextFrame;
 use crate::lib::address_space::AddressSpace;
 use crate::lib::cpu::cpu;
-use crate::lib::scheduler::scheduler;
+use crate::lib::long_message::{LongMessage, ReceiveBuffer};
+use crate::lib::notification::Notification;
+use crate::lib::realtime::Reservation;
+use crate::lib::scheduler;
 use crate::lib::traits::*;
 use crate::syscall::event::thread_exit_signal;
+use crate::syscall::SyscallOutRegisters::Unit;
 
 pub type Tid = usize;
 
@@ -24,11 +31,18 @@ pub enum PrivilegedLevel {
 pub enum Status {
   Runnable,
   Sleep,
+  // stopped by `thread_set_status` until set runnable again, unlike `Sleep` no timer ends it
+  Suspended,
   WaitForEvent,
   WaitForReply,
   WaitForRequest,
+  WaitForSend,
+  WaitForNotification,
 }
 
+// message of a caller queued by `itc_call`
+pub type ItcMessage = (usize, usize, usize, usize);
+
 #[derive(Debug)]
 #[allow(dead_code)]
 struct Inner {
@@ -40,6 +54,38 @@ struct Inner {
 
 struct InnerMut {
   status: Mutex<Status>,
+  // effective priority, the base priority raised by the callers being served
+  priority: AtomicUsize,
+  base_priority: AtomicUsize,
+  // callers waiting for reply from this thread
+  donors: Mutex<Vec<Thread>>,
+  last_core: AtomicUsize,
+  // switched in on a core, its saved context is stale until switched out
+  running: AtomicBool,
+  affinity: AtomicUsize,
+  cycles: AtomicUsize,
+  switches: AtomicUsize,
+  realtime: Mutex<Option<Reservation>>,
+  // queued callers with the badge of the capability they called, highest priority first
+  send_queue: Mutex<VecDeque<(Thread, usize, ItcMessage)>>,
+  // set by `thread_destroy` under the `send_queue` lock, no caller is queued afterwards
+  exited: AtomicBool,
+  // thread allowed to reply while waiting for reply
+  replier: AtomicUsize,
+  // signals also wake the thread from `receive`
+  notification: Mutex<Option<Notification>>,
+  // notification the thread is queued on as a waiter
+  waiting_notification: Mutex<Option<Notification>>,
+  // counter deadline of current timed wait, `usize::MAX` if none
+  timeout: AtomicUsize,
+  // where long messages sent to this thread land
+  receive_buffer: Mutex<Option<ReceiveBuffer>>,
+  // buffer passed along with the outstanding call
+  long_message: Mutex<Option<LongMessage>>,
+  // the outstanding call reports a page fault to a pager, its reply leaves the registers alone
+  pager_fault: AtomicBool,
+  // the pager could not resolve the last fault, the retried access raises an exception
+  pager_failed: AtomicBool,
   context_frame: Mutex<ContextFrame>,
 }
 
@@ -75,35 +121,312 @@ impl Thread {
     }
   }
 
+  pub fn priority(&self) -> usize {
+    self.0.inner_mut.priority.load(Relaxed)
+  }
+
+  pub fn base_priority(&self) -> usize {
+    self.0.inner_mut.base_priority.load(Relaxed)
+  }
+
+  // recompute effective priority from base, donors and queued callers, true if it changed
+  fn inherit(&self) -> bool {
+    let queued = self.0.inner_mut.send_queue.lock().iter()
+      .map(|(sender, _, _)| sender.priority())
+      .fold(self.base_priority(), usize::max);
+    let priority = self.0.inner_mut.donors.lock().iter()
+      .map(|d| d.priority())
+      .fold(queued, usize::max);
+    self.0.inner_mut.priority.swap(priority, Relaxed) != priority
+  }
+
+  // Note: called with status lock held, the thread is neither queued nor waiting for reply
+  fn add_donor(&self, donor: &Thread) {
+    self.0.inner_mut.donors.lock().push(donor.clone());
+    self.inherit();
+  }
+
+  // drop the priority inherited from `donor` once it no longer waits for reply
+  pub fn remove_donor(&self, donor: Tid) {
+    self.0.inner_mut.donors.lock().retain(|d| d.tid() != donor);
+    thread_refresh_priority(self);
+  }
+
+  pub fn last_core(&self) -> usize {
+    self.0.inner_mut.last_core.load(Relaxed)
+  }
+
+  pub fn set_last_core(&self, core_id: usize) {
+    self.0.inner_mut.last_core.store(core_id, Relaxed);
+  }
+
+  pub fn running(&self) -> bool {
+    self.0.inner_mut.running.load(SeqCst)
+  }
+
+  pub fn set_running(&self, running: bool) {
+    self.0.inner_mut.running.store(running, SeqCst);
+  }
+
+  // called by the core switching the thread out once its context is saved, queue it again if runnable
+  // Note: under the status lock, a thread woken while still running was left for this core to queue
+  pub fn switch_out(&self) {
+    let status = self.0.inner_mut.status.lock();
+    self.set_running(false);
+    if *status == Status::Runnable {
+      scheduler::add(self.clone());
+    }
+  }
+
+  pub fn affinity(&self) -> usize {
+    self.0.inner_mut.affinity.load(Relaxed)
+  }
+
+  pub fn allowed_on(&self, core_id: usize) -> bool {
+    self.affinity() & (1 << core_id) != 0
+  }
+
+  pub fn cycles(&self) -> usize {
+    self.0.inner_mut.cycles.load(Relaxed)
+  }
+
+  pub fn switches(&self) -> usize {
+    self.0.inner_mut.switches.load(Relaxed)
+  }
+
+  // called when the thread is switched in
+  pub fn account_switch(&self) {
+    self.0.inner_mut.switches.fetch_add(1, Relaxed);
+  }
+
+  // called when the thread is switched out after running `cycles`
+  pub fn account_cycles(&self, cycles: usize) {
+    self.0.inner_mut.cycles.fetch_add(cycles, Relaxed);
+    if let Some(a) = self.address_space() {
+      a.account_cycles(cycles);
+    }
+  }
+
+  pub fn is_realtime(&self) -> bool {
+    self.0.inner_mut.realtime.lock().is_some()
+  }
+
+  pub fn reservation(&self) -> Option<Reservation> {
+    *self.0.inner_mut.realtime.lock()
+  }
+
+  pub fn map_reservation<F, T>(&self, f: F) -> T where F: FnOnce(&mut Option<Reservation>) -> T {
+    let mut reservation = self.0.inner_mut.realtime.lock();
+    f(&mut *reservation)
+  }
+
   pub fn runnable(&self) -> bool {
     let lock = self.0.inner_mut.status.lock();
     *lock == Status::Runnable
   }
 
-  pub fn wait_for_reply<F>(&self, f: F) -> bool where F: FnOnce() {
+  pub fn set_replier(&self, replier: Tid) {
+    self.0.inner_mut.replier.store(replier, Relaxed);
+  }
+
+  pub fn wait_for_reply<F>(&self, replier: Tid, f: F) -> bool where F: FnOnce() {
     let mut status = self.0.inner_mut.status.lock();
-    if *status == Status::WaitForReply {
+    if *status == Status::WaitForReply && self.0.inner_mut.replier.load(Relaxed) == replier {
       f();
       *status = Status::Runnable;
-      scheduler().add_front(self.clone());
+      scheduler::add_front(self.clone());
       true
     } else {
       false
     }
   }
 
-  pub fn wait_for_request<F>(&self, f: F) -> bool where F: FnOnce() {
+  // deliver by `f` if the thread waits for request, otherwise queue `sender` until it receives
+  // Note: fails if the thread has exited since it was looked up, `sender` is made runnable again
+  pub fn wait_for_request_or_enqueue<F>(&self, f: F, sender: &Thread, badge: usize, message: ItcMessage) -> Result<bool, usize> where F: FnOnce() {
     let mut status = self.0.inner_mut.status.lock();
     if *status == Status::WaitForRequest {
       f();
+      self.add_donor(sender);
       *status = Status::Runnable;
-      scheduler().add_front(self.clone());
+      scheduler::add_front(self.clone());
+      Ok(true)
+    } else {
+      let mut queue = self.0.inner_mut.send_queue.lock();
+      if self.0.inner_mut.exited.load(Relaxed) {
+        drop(queue);
+        *sender.0.inner_mut.status.lock() = Status::Runnable;
+        return Err(ERROR_INVARG);
+      }
+      enqueue_sender(&mut queue, (sender.clone(), badge, message));
+      drop(queue);
+      drop(status);
+      // Note: a queued caller donates its priority as well, so that the thread gets to receive it
+      thread_refresh_priority(self);
+      Ok(false)
+    }
+  }
+
+  // move a queued caller whose priority changed to its new place
+  fn requeue_sender(&self, sender: Tid) {
+    let mut queue = self.0.inner_mut.send_queue.lock();
+    if let Some(i) = queue.iter().position(|(t, _, _)| t.tid() == sender) {
+      let entry = queue.remove(i).unwrap();
+      enqueue_sender(&mut queue, entry);
+    }
+  }
+
+  // take pending bits of bound notification or the first queued sender (which then waits for reply)
+  // with its badge, or wait for request if there is neither
+  pub fn receive(&self) -> Option<(Tid, usize, ItcMessage)> {
+    let mut status = self.0.inner_mut.status.lock();
+    if let Some(n) = self.0.inner_mut.notification.lock().as_ref() {
+      let word = n.take();
+      if word != 0 {
+        return Some((rpabi::cap::CAP_NOTIFICATION_SENDER, rpabi::cap::CAP_BADGE_NONE, (word, 0, 0, 0)));
+      }
+    }
+    // Note: queue lock is not held across `add_donor`, which looks at the queue
+    loop {
+      let (sender, badge, message) = match self.0.inner_mut.send_queue.lock().pop_front() {
+        Some(entry) => entry,
+        None => break,
+      };
+      let mut sender_status = sender.0.inner_mut.status.lock();
+      // Note: sender may have exited while queued
+      if *sender_status == Status::WaitForSend {
+        *sender_status = Status::WaitForReply;
+        self.add_donor(&sender);
+        return Some((sender.tid(), badge, message));
+      }
+    }
+    *status = Status::WaitForRequest;
+    None
+  }
+
+  // wake from `receive` if `f` delivers a notification
+  pub fn notify_request<F>(&self, f: F) -> bool where F: FnOnce() -> bool {
+    let mut status = self.0.inner_mut.status.lock();
+    if *status == Status::WaitForRequest && f() {
+      *status = Status::Runnable;
+      scheduler::add(self.clone());
       true
     } else {
       false
     }
   }
 
+  pub fn bind_notification(&self, n: Notification) {
+    *self.0.inner_mut.notification.lock() = Some(n);
+  }
+
+  // take pending bits of `n`, or wait for it to be signalled if there is none
+  pub fn wait_for_notification(&self, n: &Notification) -> Option<usize> {
+    let mut status = self.0.inner_mut.status.lock();
+    match n.take_or_enqueue(self) {
+      0 => {
+        *status = Status::WaitForNotification;
+        *self.0.inner_mut.waiting_notification.lock() = Some(n.clone());
+        None
+      }
+      word => Some(word),
+    }
+  }
+
+  pub fn wake_from_notification<F>(&self, f: F) -> bool where F: FnOnce() -> bool {
+    let mut status = self.0.inner_mut.status.lock();
+    if *status == Status::WaitForNotification && f() {
+      *status = Status::Runnable;
+      self.0.inner_mut.waiting_notification.lock().take();
+      scheduler::add(self.clone());
+      true
+    } else {
+      false
+    }
+  }
+
+  pub fn wake_from_send<F>(&self, f: F) -> bool where F: FnOnce() {
+    let mut status = self.0.inner_mut.status.lock();
+    if *status == Status::WaitForSend {
+      f();
+      *status = Status::Runnable;
+      scheduler::add(self.clone());
+      true
+    } else {
+      false
+    }
+  }
+
+  pub fn timeout(&self) -> usize {
+    self.0.inner_mut.timeout.load(Relaxed)
+  }
+
+  pub fn set_timeout(&self, deadline: usize) {
+    self.0.inner_mut.timeout.store(deadline, Relaxed);
+  }
+
+  // end a timed wait, a sleep completes normally while IPC fails with `ERROR_TIMEOUT`
+  pub fn wake_from_timeout(&self) -> bool {
+    let mut status = self.0.inner_mut.status.lock();
+    let result = match *status {
+      Status::Sleep => Ok(Unit),
+      Status::WaitForRequest | Status::WaitForReply | Status::WaitForSend => Err(ERROR_TIMEOUT),
+      _ => return false,
+    };
+    let queued = *status == Status::WaitForSend;
+    let donating = *status == Status::WaitForReply;
+    self.map_with_context(|ctx| ctx.set_syscall_result(&result));
+    *status = Status::Runnable;
+    scheduler::add(self.clone());
+    drop(status);
+    // Note: forget the callee, so that a late reply is denied rather than taken as the reply to a later call
+    let replier = self.0.inner_mut.replier.swap(0, Relaxed);
+    // Note: still queued by the thread it called, status has been changed so that it is skipped meanwhile
+    if queued {
+      if let Some(target) = thread_lookup(replier) {
+        target.0.inner_mut.send_queue.lock().retain(|(sender, _, _)| sender.tid() != self.tid());
+        thread_refresh_priority(&target);
+      }
+    }
+    if donating {
+      if let Some(target) = thread_lookup(replier) {
+        target.remove_donor(self.tid());
+        crate::lib::long_message::cancel(self, &target);
+      }
+    }
+    true
+  }
+
+  pub fn receive_buffer(&self) -> Option<ReceiveBuffer> {
+    *self.0.inner_mut.receive_buffer.lock()
+  }
+
+  pub fn set_receive_buffer(&self, buffer: Option<ReceiveBuffer>) {
+    *self.0.inner_mut.receive_buffer.lock() = buffer;
+  }
+
+  pub fn map_long_message<F, T>(&self, f: F) -> T where F: FnOnce(&mut Option<LongMessage>) -> T {
+    let mut long_message = self.0.inner_mut.long_message.lock();
+    f(&mut *long_message)
+  }
+
+  pub fn set_pager_fault(&self, pager_fault: bool) {
+    self.0.inner_mut.pager_fault.store(pager_fault, Relaxed);
+  }
+
+  // clear the flag set by `set_pager_fault`, returns whether it was set
+  pub fn take_pager_fault(&self) -> bool {
+    self.0.inner_mut.pager_fault.swap(false, Relaxed)
+  }
+
+  pub fn set_pager_failed(&self, failed: bool) {
+    self.0.inner_mut.pager_failed.store(failed, Relaxed);
+  }
+
+  pub fn take_pager_failed(&self) -> bool {
+    self.0.inner_mut.pager_failed.swap(false, Relaxed)
+  }
+
   pub fn address_space(&self) -> Option<AddressSpace> {
     self.0.inner.address_space.clone()
   }
@@ -143,6 +466,25 @@ pub fn new_user(pc: usize, sp: usize, arg: usize, a: AddressSpace, parent: Optio
     },
     inner_mut: InnerMut {
       status: Mutex::new(Status::Sleep),
+      priority: AtomicUsize::new(THREAD_PRIORITY_DEFAULT),
+      base_priority: AtomicUsize::new(THREAD_PRIORITY_DEFAULT),
+      donors: Mutex::new(Vec::new()),
+      last_core: AtomicUsize::new(crate::arch::Arch::core_id()),
+      running: AtomicBool::new(false),
+      affinity: AtomicUsize::new(THREAD_AFFINITY_ANY),
+      cycles: AtomicUsize::new(0),
+      switches: AtomicUsize::new(0),
+      realtime: Mutex::new(None),
+      send_queue: Mutex::new(VecDeque::new()),
+      exited: AtomicBool::new(false),
+      replier: AtomicUsize::new(0),
+      notification: Mutex::new(None),
+      waiting_notification: Mutex::new(None),
+      timeout: AtomicUsize::new(usize::MAX),
+      receive_buffer: Mutex::new(None),
+      long_message: Mutex::new(None),
+      pager_fault: AtomicBool::new(false),
+      pager_failed: AtomicBool::new(false),
       context_frame: Mutex::new(ContextFrame::new(pc, sp, arg, false)),
     },
   }));
@@ -162,6 +504,25 @@ pub fn new_kernel(pc: usize, sp: usize, arg: usize) -> Thread {
     },
     inner_mut: InnerMut {
       status: Mutex::new(Status::Sleep),
+      priority: AtomicUsize::new(THREAD_PRIORITY_DEFAULT),
+      base_priority: AtomicUsize::new(THREAD_PRIORITY_DEFAULT),
+      donors: Mutex::new(Vec::new()),
+      last_core: AtomicUsize::new(crate::arch::Arch::core_id()),
+      running: AtomicBool::new(false),
+      affinity: AtomicUsize::new(THREAD_AFFINITY_ANY),
+      cycles: AtomicUsize::new(0),
+      switches: AtomicUsize::new(0),
+      realtime: Mutex::new(None),
+      send_queue: Mutex::new(VecDeque::new()),
+      exited: AtomicBool::new(false),
+      replier: AtomicUsize::new(0),
+      notification: Mutex::new(None),
+      waiting_notification: Mutex::new(None),
+      timeout: AtomicUsize::new(usize::MAX),
+      receive_buffer: Mutex::new(None),
+      long_message: Mutex::new(None),
+      pager_fault: AtomicBool::new(false),
+      pager_failed: AtomicBool::new(false),
       context_frame: Mutex::new(ContextFrame::new(pc, sp, arg, true)),
     },
   }));
@@ -182,6 +543,49 @@ pub fn thread_destroy(t: Thread) {
       crate::lib::cpu::cpu().set_running_thread(None);
     }
   }
+  crate::lib::realtime::detach(&t);
+  let (queued, donating) = {
+    let mut status = t.0.inner_mut.status.lock();
+    let queued = *status == Status::WaitForSend;
+    // Note: keep queued senders and notification waiters from being woken after exit
+    if *status == Status::WaitForSend || *status == Status::WaitForNotification {
+      *status = Status::Sleep;
+    }
+    (queued, *status == Status::WaitForReply)
+  };
+  // Note: no longer lends its priority to the thread it is queued on
+  if queued {
+    if let Some(replier) = thread_lookup(t.0.inner_mut.replier.load(Relaxed)) {
+      replier.0.inner_mut.send_queue.lock().retain(|(sender, _, _)| sender.tid() != t.tid());
+      thread_refresh_priority(&replier);
+    }
+  }
+  if donating {
+    if let Some(replier) = thread_lookup(t.0.inner_mut.replier.load(Relaxed)) {
+      replier.remove_donor(t.tid());
+      crate::lib::long_message::cancel(&t, &replier);
+    }
+  }
+  t.0.inner_mut.donors.lock().clear();
+  t.0.inner_mut.notification.lock().take();
+  if let Some(n) = t.0.inner_mut.waiting_notification.lock().take() {
+    n.remove_waiter(t.tid());
+  }
+  // Note: callers still queued on an exiting thread would never be received
+  let senders: Vec<(Thread, usize, ItcMessage)> = {
+    let mut queue = t.0.inner_mut.send_queue.lock();
+    t.0.inner_mut.exited.store(true, Relaxed);
+    queue.drain(..).collect()
+  };
+  for (sender, _, _) in senders {
+    sender.wake_from_send(|| {
+      // Note: a faulting thread retries the access and finds the pager gone
+      if !sender.take_pager_fault() {
+        sender.map_with_context(|ctx| ctx.set_syscall_result(&Err(ERROR_INVARG)));
+      }
+    });
+  }
+  crate::syscall::server::service_exit(t.tid());
   if let Some(parent) = t.parent() {
     thread_exit_signal(t.tid(), parent);
   }
@@ -192,7 +596,66 @@ pub fn thread_destroy(t: Thread) {
 pub fn thread_wake(t: &Thread) {
   let mut status = t.0.inner_mut.status.lock();
   *status = Status::Runnable;
-  scheduler().add(t.clone());
+  scheduler::add(t.clone());
+}
+
+pub fn thread_set_priority(t: &Thread, priority: usize) {
+  t.0.inner_mut.base_priority.store(priority, Relaxed);
+  thread_refresh_priority(t);
+}
+
+// bound on the chain of calls a priority change is passed along
+const PRIORITY_DONATION_DEPTH: usize = 16;
+
+// recompute effective priority of `t`, and of the threads it transitively waits for reply from
+fn thread_refresh_priority(t: &Thread) {
+  let mut t = t.clone();
+  for _ in 0..PRIORITY_DONATION_DEPTH {
+    let status = t.0.inner_mut.status.lock();
+    if !t.inherit() {
+      return;
+    }
+    let queued = match *status {
+      Status::Runnable => {
+        // Note: move a queued thread to its new band
+        if let Some(t) = scheduler::remove(t.tid()) {
+          scheduler::add(t);
+        }
+        return;
+      }
+      Status::WaitForReply => false,
+      Status::WaitForSend => true,
+      _ => return,
+    };
+    drop(status);
+    match thread_lookup(t.0.inner_mut.replier.load(Relaxed)) {
+      Some(replier) => {
+        if queued {
+          replier.requeue_sender(t.tid());
+        }
+        t = replier;
+      }
+      None => return,
+    }
+  }
+}
+
+// insert behind queued callers of the same or higher priority
+fn enqueue_sender(queue: &mut VecDeque<(Thread, usize, ItcMessage)>, entry: (Thread, usize, ItcMessage)) {
+  let priority = entry.0.priority();
+  let i = queue.iter().position(|(t, _, _)| t.priority() < priority).unwrap_or(queue.len());
+  queue.insert(i, entry);
+}
+
+pub fn thread_set_affinity(t: &Thread, mask: usize) {
+  let status = t.0.inner_mut.status.lock();
+  t.0.inner_mut.affinity.store(mask, Relaxed);
+  // Note: move a queued thread to an allowed core, a running one migrates on its next schedule
+  if *status == Status::Runnable {
+    if let Some(t) = scheduler::remove(t.tid()) {
+      scheduler::add(t);
+    }
+  }
 }
 
 pub fn thread_sleep(t: &Thread, reason: Status) {
@@ -1,3 +1,10 @@
+use alloc::collections::BTreeSet;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use spin::Mutex;
+
+use crate::lib::thread::{Status, Thread, thread_lookup, thread_sleep, Tid};
+
 #[allow(dead_code)]
 const TIMER_SEC_TO_MS: usize = 1000;
 #[allow(dead_code)]
@@ -24,9 +31,122 @@ pub fn current_sec() -> usize {
   count / freq
 }
 
+pub fn us_to_count(us: usize) -> usize {
+  let freq = crate::driver::timer::frequency();
+  let count = us as u128 * freq as u128 / TIMER_SEC_TO_US as u128;
+  count.min(usize::MAX as u128) as usize
+}
+
+// length of a time slice, a build-time setting (`make TIME_SLICE_US=...`) or the architecture default
+static TIME_SLICE_US: AtomicUsize = AtomicUsize::new(crate::driver::timer::TIMER_DEFAULT_TIME_SLICE_US);
+
+// called by core 0 before other cores are launched
+pub fn init() {
+  // Note: `RUSTPI_TIME_SLICE_US` is passed by `make TIME_SLICE_US=...` and compiled in,
+  //       changing it needs a rebuild, there is no boot argument for it
+  if let Some(us) = option_env!("RUSTPI_TIME_SLICE_US").and_then(|s| s.parse::<usize>().ok()) {
+    if us > 0 {
+      TIME_SLICE_US.store(us, Ordering::Relaxed);
+    }
+  }
+  info!("time slice {} us", TIME_SLICE_US.load(Ordering::Relaxed));
+}
+
+pub fn time_slice_count() -> usize {
+  us_to_count(TIME_SLICE_US.load(Ordering::Relaxed))
+}
+
+// sleeping threads ordered by deadline (in counter ticks)
+static TIMER_QUEUE: Mutex<BTreeSet<(usize, Tid)>> = Mutex::new(BTreeSet::new());
+
+// first deadline of `TIMER_QUEUE`, readable without taking the lock
+static TIMER_QUEUE_FIRST: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+fn update_first(queue: &BTreeSet<(usize, Tid)>) {
+  let first = queue.first().map_or(usize::MAX, |&(deadline, _)| deadline);
+  TIMER_QUEUE_FIRST.store(first, Ordering::Relaxed);
+}
+
+fn arm(queue: &mut BTreeSet<(usize, Tid)>, t: &Thread, deadline: usize) {
+  t.set_timeout(deadline);
+  queue.insert((deadline, t.tid()));
+  update_first(queue);
+}
+
+// put `t` into `Status::Sleep` until counter reaches `deadline`
+pub fn sleep_until(t: &Thread, deadline: usize) {
+  let mut queue = TIMER_QUEUE.lock();
+  arm(&mut queue, t, deadline);
+  // Note: hold the queue so that no core expires the entry before `t` actually sleeps
+  thread_sleep(t, Status::Sleep);
+  drop(queue);
+  program();
+}
+
+// fail the IPC wait `t` has just entered with `ERROR_TIMEOUT` after `us`, 0 waits forever
+pub fn ipc_timeout(t: &Thread, us: usize) {
+  if us == 0 {
+    t.set_timeout(usize::MAX);
+    return;
+  }
+  let deadline = crate::driver::timer::counter().saturating_add(us_to_count(us));
+  let mut queue = TIMER_QUEUE.lock();
+  arm(&mut queue, t, deadline);
+  drop(queue);
+  program();
+}
+
+// wake all threads whose deadline has passed
+fn expire() {
+  let now = crate::driver::timer::counter();
+  let mut queue = TIMER_QUEUE.lock();
+  while let Some(&(deadline, tid)) = queue.first() {
+    if deadline > now {
+      break;
+    }
+    queue.pop_first();
+    update_first(&queue);
+    // Note: thread may have exited, or been woken by other means and entered another wait
+    if let Some(t) = thread_lookup(tid) {
+      if t.timeout() == deadline {
+        t.set_timeout(usize::MAX);
+        t.wake_from_timeout();
+      }
+    }
+  }
+}
+
+// arm the timer of this core for the end of time slice, the nearest deadline or real-time event
+// Note: an idle core has no time slice to end (tickless), it stops the timer if nothing is due
+pub fn program() {
+  let core = crate::lib::cpu::cpu();
+  let mut next = if core.is_idle() {
+    usize::MAX
+  } else {
+    crate::driver::timer::counter() + time_slice_count()
+  };
+  next = next.min(TIMER_QUEUE_FIRST.load(Ordering::Relaxed));
+  if let Some(event) = core.realtime_next_event() {
+    next = next.min(event);
+  }
+  if next == usize::MAX {
+    crate::driver::timer::stop();
+  } else {
+    crate::driver::timer::next_at(next);
+  }
+}
+
 pub fn interrupt() {
-  crate::driver::timer::next();
-  crate::lib::cpu::cpu().schedule();
+  expire();
+  let core = crate::lib::cpu::cpu();
+  core.charge_realtime();
+  core.realtime().release(crate::driver::timer::counter());
+  if core.tick() % crate::lib::scheduler::SCHEDULER_BALANCE_TICKS == 0 {
+    crate::lib::scheduler::balance();
+  }
+  core.schedule();
+  // Note: after schedule, so that budget of a newly picked real-time thread is enforced
+  program();
 }
 
 #[cfg(target_arch = "aarch64")]
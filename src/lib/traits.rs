@@ -12,10 +12,19 @@ pub trait ArchTrait {
   // context filled in CONTEXT_FRAME, and its
   // page table installed at low address space.
   fn invalidate_tlb();
+  // invalidate the entry of page `va` tagged with hardware ASID `asid`, and every entry tagged with `asid`
+  fn invalidate_tlb_page(va: usize, asid: usize);
+  fn invalidate_tlb_asid(asid: usize);
+  // the two above reach the TLB of every core, not just the current one
+  const TLB_BROADCAST: bool;
   fn wait_for_interrupt();
   fn nop();
   fn fault_address() -> usize;
+  // access which caused the page fault, `rpabi::pager` bits
+  fn fault_access() -> usize;
   fn core_id() -> usize;
+  // width of the ASID tag the hardware keeps in TLB entries
+  fn asid_bits() -> usize;
 }
 
 pub trait ContextFrameTrait {
@@ -0,0 +1,130 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use rpabi::syscall::error::*;
+
+use crate::lib::capability::Capability;
+use crate::mm::page_table::EntryAttribute;
+
+pub type Error = usize;
+
+// range `[start, end)` reserved by `vma_reserve`, its pages are allocated zeroed on first access
+// unless faults on it are sent to a pager
+#[derive(Debug, Clone)]
+pub struct Vma {
+  pub start: usize,
+  pub end: usize,
+  pub attr: EntryAttribute,
+  pub pager: Option<Capability>,
+}
+
+impl Vma {
+  pub fn contains(&self, va: usize) -> bool {
+    self.start <= va && va < self.end
+  }
+}
+
+// non-overlapping areas keyed by start address
+#[derive(Debug, Clone)]
+pub struct VmaList(BTreeMap<usize, Vma>);
+
+impl VmaList {
+  pub const fn new() -> Self {
+    VmaList(BTreeMap::new())
+  }
+
+  pub fn find(&self, va: usize) -> Option<Vma> {
+    self.0.range(..=va).next_back().map(|(_, vma)| vma.clone()).filter(|vma| vma.contains(va))
+  }
+
+  fn overlaps(&self, start: usize, end: usize) -> bool {
+    self.0.range(..end).next_back().map_or(false, |(_, vma)| vma.end > start)
+  }
+
+  // every page of `[start, end)` belongs to some area
+  fn covers(&self, start: usize, end: usize) -> bool {
+    let mut va = start;
+    while va < end {
+      match self.find(va) {
+        Some(vma) => va = vma.end,
+        None => return false,
+      }
+    }
+    true
+  }
+
+  // make `at` a boundary between areas
+  fn split(&mut self, at: usize) {
+    if let Some(vma) = self.find(at) {
+      if vma.start != at {
+        self.0.insert(vma.start, Vma { end: at, ..vma.clone() });
+        self.0.insert(at, Vma { start: at, ..vma });
+      }
+    }
+  }
+
+  // join every pair of adjacent areas meeting in `[start, end]` if nothing tells them apart
+  fn merge(&mut self, start: usize, end: usize) {
+    let bounds: Vec<usize> = self.0.range(start..=end).map(|(start, _)| *start).collect();
+    // Note: from the highest, so that the areas left to look at keep their start
+    for at in bounds.into_iter().rev() {
+      let next = match self.0.get(&at) {
+        Some(vma) if vma.pager.is_none() => vma.clone(),
+        _ => continue,
+      };
+      if let Some((_, prev)) = self.0.range_mut(..at).next_back() {
+        if prev.end == at && prev.attr == next.attr && prev.pager.is_none() {
+          prev.end = next.end;
+          self.0.remove(&at);
+        }
+      }
+    }
+  }
+
+  pub fn reserve(&mut self, start: usize, end: usize, attr: EntryAttribute) -> Result<(), Error> {
+    if self.overlaps(start, end) {
+      return Err(ERROR_INVARG);
+    }
+    self.0.insert(start, Vma { start, end, attr, pager: None });
+    self.merge(start, end);
+    Ok(())
+  }
+
+  pub fn protect(&mut self, start: usize, end: usize, attr: EntryAttribute) -> Result<(), Error> {
+    if !self.covers(start, end) {
+      return Err(ERROR_MEM_NOT_MAP);
+    }
+    self.split(start);
+    self.split(end);
+    for (_, vma) in self.0.range_mut(start..end) {
+      vma.attr = attr;
+    }
+    self.merge(start, end);
+    Ok(())
+  }
+
+  pub fn set_pager(&mut self, start: usize, end: usize, pager: Capability) -> Result<(), Error> {
+    if !self.covers(start, end) {
+      return Err(ERROR_MEM_NOT_MAP);
+    }
+    self.split(start);
+    self.split(end);
+    for (_, vma) in self.0.range_mut(start..end) {
+      vma.pager = Some(pager.clone());
+    }
+    Ok(())
+  }
+
+  pub fn release(&mut self, start: usize, end: usize) -> Result<(), Error> {
+    if !self.covers(start, end) {
+      return Err(ERROR_MEM_NOT_MAP);
+    }
+    self.split(start);
+    self.split(end);
+    let released: Vec<usize> = self.0.range(start..end).map(|(start, _)| *start).collect();
+    for start in released {
+      self.0.remove(&start);
+    }
+    Ok(())
+  }
+}
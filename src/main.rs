@@ -148,6 +148,7 @@ pub unsafe fn main(core_id: arch::CoreId) -> ! {
     info!("heap init ok");
     mm::page_pool::init();
     info!("page pool init ok");
+    lib::timer::init();
 
     board::launch_other_cores();
     info!("launched other cores");
@@ -188,17 +189,6 @@ pub unsafe fn main(core_id: arch::CoreId) -> ! {
                            mm::Frame::from(stack_frame),
                            mm::page_table::EntryAttribute::user_default()).unwrap();
 
-    #[cfg(feature = "k210")]
-      {
-        let dma_frame = mm::page_pool::page_alloc().expect("failed to allocate trusted dma frame");
-        let dma_frame_no_cache = dma_frame.pa() - 0x40000000;
-        info!("dma_frame {:016x}", dma_frame_no_cache);
-        page_table.insert_page(0x8_0000_0000,
-                               mm::Frame::Device(dma_frame_no_cache),
-                               mm::page_table::EntryAttribute::user_device()).unwrap();
-        core::mem::forget(dma_frame);
-      }
-
     info!("user stack ok");
     let t = crate::lib::thread::new_user(
       entry,
@@ -6,3 +6,4 @@ pub mod heap;
 pub mod config;
 pub mod page_table;
 pub mod page_fault;
+pub mod tlb;
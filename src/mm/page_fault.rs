@@ -1,11 +1,106 @@
+use alloc::sync::Arc;
+
 use rpabi::{CONFIG_USER_STACK_BTM, CONFIG_USER_STACK_TOP};
 
 use crate::arch::PAGE_SIZE;
+use crate::lib::address_space::AddressSpace;
 use crate::lib::cpu::cpu;
 use crate::lib::traits::*;
-use crate::mm::page_table::{PageTableEntryAttrTrait, PageTableTrait};
+use crate::mm::Frame;
+use crate::mm::page_table::{Entry, EntryAttribute, PageTableEntryAttrTrait, PageTableTrait};
 use crate::util::*;
 
+// make a copy-on-write page writable, taking over its frame if no one else maps it or copying it otherwise
+pub fn copy_on_write(a: &AddressSpace, va: usize) -> bool {
+  a.map_vmas(|vmas| {
+    // Note: an area protected read-only keeps its pages shared
+    if vmas.find(va).map_or(false, |vma| !vma.attr.writable()) {
+      return false;
+    }
+    copy_page(a, va)
+  })
+}
+
+fn copy_page(a: &AddressSpace, va: usize) -> bool {
+  let pt = a.page_table();
+  let (attr, size) = match pt.lookup_page(va) {
+    Some(entry) if entry.attribute().copy_on_write() => (entry.attribute(), entry.size()),
+    _ => return false,
+  };
+  // Note: a block is copied as a whole
+  let va = round_down(va, size);
+  let frame = match pt.lookup_user_page(va) {
+    Some(Frame::PhysicalMemory(frame)) => frame,
+    _ => return false,
+  };
+  let attr = EntryAttribute::new(true, attr.u_readable(), false, attr.k_executable(), attr.u_executable(), false, false);
+  // Note: one reference is held by the page table and one by `frame`
+  let uf = if Arc::strong_count(&frame) <= 2 {
+    Frame::PhysicalMemory(frame)
+  } else {
+    // Note: the copy is charged before the shared page it replaces is dropped
+    if a.charge(size / PAGE_SIZE).is_err() {
+      warn!("copy-on-write page over limit of AS{}", a.asid());
+      return false;
+    }
+    let copy = if size == PAGE_SIZE {
+      crate::mm::page_pool::page_alloc()
+    } else {
+      crate::mm::page_pool::block_alloc(size)
+    };
+    match copy {
+      Ok(copy) => {
+        unsafe {
+          core::ptr::copy_nonoverlapping(frame.kva() as *const u8, copy.kva() as *mut u8, size);
+        }
+        Frame::from(copy)
+      }
+      Err(_) => {
+        warn!("copy-on-write page allocate oom");
+        return false;
+      }
+    }
+  };
+  pt.insert_page(va, uf, attr).is_ok()
+}
+
+// map a zeroed page at unmapped `va` if it lies in an area reserved in `a`
+pub fn demand_zero(a: &AddressSpace, va: usize) -> bool {
+  a.map_vmas(|vmas| {
+    let vma = match vmas.find(va) {
+      Some(vma) if vma.attr.u_readable() && vma.pager.is_none() => vma,
+      _ => return false,
+    };
+    let pt = a.page_table();
+    // Note: another thread of the address space may have faulted on it first
+    if pt.lookup_page(va).is_some() {
+      return true;
+    }
+    if a.charge(1).is_err() {
+      warn!("demand zero page over limit of AS{}", a.asid());
+      return false;
+    }
+    match crate::mm::page_pool::page_alloc() {
+      Ok(frame) => {
+        frame.zero();
+        pt.insert_page(va, Frame::from(frame), vma.attr).is_ok()
+      }
+      Err(_) => {
+        warn!("demand zero page allocate oom");
+        false
+      }
+    }
+  })
+}
+
+// entry of page `va` in `a`, populating a reserved area the way a fault would
+pub fn populate(a: &AddressSpace, va: usize) -> Option<Entry> {
+  match a.page_table().lookup_page(va) {
+    None if demand_zero(a, va) => a.page_table().lookup_page(va),
+    entry => entry,
+  }
+}
+
 pub fn handle() {
   let t = cpu().running_thread();
   match t {
@@ -22,10 +117,30 @@ pub fn handle() {
         let addr = crate::arch::Arch::fault_address();
         let va = round_down(addr, PAGE_SIZE);
 
+        // Note: a copy-on-write page is mapped read-only, any fault on it is a write
+        if copy_on_write(&a, va) {
+          return;
+        }
+
+        if a.page_table().lookup_page(va).is_none() && demand_zero(&a, va) {
+          return;
+        }
+
+        // Note: the pager resolves the fault and replies, the access is retried when the thread runs again
+        if let Some(pager) = a.map_vmas(|vmas| vmas.find(va)).and_then(|vma| vma.pager).filter(|_| !t.take_pager_failed()) {
+          let access = crate::arch::Arch::fault_access();
+          if crate::syscall::ipc::pager_call(&pager, (addr, access, a.asid() as usize, 0)).is_ok() {
+            return;
+          }
+        }
+
         // NOTE: allocate stack region automatically
         if addr > CONFIG_USER_STACK_BTM && addr < CONFIG_USER_STACK_TOP {
           let pt = a.page_table();
           match pt.lookup_page(va) {
+            None if a.charge(1).is_err() => {
+              warn!("stack page over limit of AS{}", a.asid());
+            }
             None => {
               if let Ok(frame) = crate::mm::page_pool::page_alloc() {
                 frame.zero();
@@ -6,13 +6,24 @@ use crate::lib::traits::*;
 #[derive(Debug)]
 pub struct PhysicalFrame {
   pa: usize,
+  size: usize,
 }
 
 impl PhysicalFrame {
   pub fn new(pa: usize) -> Self {
     assert_eq!(pa % PAGE_SIZE, 0);
     PhysicalFrame {
-      pa
+      pa,
+      size: PAGE_SIZE,
+    }
+  }
+
+  // contiguous `size` bytes at `pa`, mapped as one block
+  pub fn block(pa: usize, size: usize) -> Self {
+    assert_eq!(pa % size, 0);
+    PhysicalFrame {
+      pa,
+      size,
     }
   }
 
@@ -24,9 +35,13 @@ impl PhysicalFrame {
     self.pa
   }
 
+  pub fn size(&self) -> usize {
+    self.size
+  }
+
   pub fn zero(&self) {
     unsafe {
-      core::ptr::write_bytes(self.kva() as *mut u8, 0, PAGE_SIZE);
+      core::ptr::write_bytes(self.kva() as *mut u8, 0, self.size);
     }
   }
 }
@@ -56,11 +71,18 @@ impl Frame {
       Frame::Device(pa) => { *pa }
     }
   }
+
+  pub fn size(&self) -> usize {
+    match self {
+      Frame::PhysicalMemory(frame) => { frame.size }
+      Frame::Device(_) => { PAGE_SIZE }
+    }
+  }
 }
 
 impl Drop for PhysicalFrame {
   fn drop(&mut self) {
     // info!("drop frame {:016x}", self.pa);
-    super::page_pool::page_free(self.pa).expect("physical page drop failed");
+    super::page_pool::block_free(self.pa, self.size).expect("physical page drop failed");
   }
 }
@@ -1,10 +1,11 @@
-use alloc::collections::VecDeque;
-use core::ops::Range;
+use alloc::vec::Vec;
 
-use rpabi::syscall::error::ERROR_OOM;
+use rpabi::syscall::error::{ERROR_INVARG, ERROR_OOM};
+use rpbuddy::{order_of, PagePool};
 use spin::{Mutex, Once};
 
 use crate::arch::*;
+use crate::lib::traits::*;
 use crate::mm::PhysicalFrame;
 
 pub type Error = usize;
@@ -21,36 +22,15 @@ pub type Error = usize;
 //   }
 // }
 
+const_assert_eq!(rpbuddy::PAGE_SIZE, PAGE_SIZE);
 
-struct PagePool {
-  free: VecDeque<usize>,
-}
-
-impl PagePool {
-  pub fn init(&mut self, range: Range<usize>) {
-    assert_eq!(range.start % PAGE_SIZE, 0);
-    assert_eq!(range.end % PAGE_SIZE, 0);
-    unsafe { core::ptr::write_bytes(range.start as *mut u8, 0, range.len()); }
-    for pa in range.step_by(PAGE_SIZE) {
-      self.free.push_back(pa);
-    }
-  }
-
-  pub fn allocate(&mut self) -> Result<PhysicalFrame, Error> {
-    if let Some(pa) = self.free.pop_front() {
-      Ok(PhysicalFrame::new(pa))
-    } else {
-      Err(ERROR_OOM)
-    }
-  }
-
-  pub fn free(&mut self, pa: usize) -> Result<(), Error> {
-    self.free.push_back(pa);
-    Ok(())
+fn error(e: rpbuddy::Error) -> Error {
+  match e {
+    rpbuddy::Error::InvalidArgument => ERROR_INVARG,
+    rpbuddy::Error::OutOfMemory => ERROR_OOM,
   }
 }
 
-
 static PAGE_POOL: Once<Mutex<PagePool>> = Once::new();
 
 fn page_pool() -> &'static Mutex<PagePool> {
@@ -59,19 +39,45 @@ fn page_pool() -> &'static Mutex<PagePool> {
 
 pub fn init() {
   let range = super::config::paged_range();
-  PAGE_POOL.call_once(|| {Mutex::new(PagePool {
-    free: VecDeque::new(),
-  })});
+  unsafe { core::ptr::write_bytes(range.start as *mut u8, 0, range.len()); }
+  PAGE_POOL.call_once(|| Mutex::new(PagePool::new(|pa| pa.pa2kva())));
   let mut pool = page_pool().lock();
   pool.init(range);
 }
 
 pub fn page_alloc() -> Result<PhysicalFrame, Error> {
   let mut pool = page_pool().lock();
-  pool.allocate()
+  pool.allocate(0).map(PhysicalFrame::new).map_err(error)
+}
+
+// `size` bytes of contiguous memory aligned to `size`
+pub fn block_alloc(size: usize) -> Result<PhysicalFrame, Error> {
+  assert!(size % PAGE_SIZE == 0 && (size / PAGE_SIZE).is_power_of_two());
+  let mut pool = page_pool().lock();
+  pool.allocate(order_of(size / PAGE_SIZE)).map(|pa| PhysicalFrame::block(pa, size)).map_err(error)
+}
+
+// `count` contiguous pages, each freed on its own
+pub fn contiguous_alloc(count: usize) -> Result<Vec<PhysicalFrame>, Error> {
+  if count == 0 {
+    return Err(ERROR_INVARG);
+  }
+  let order = order_of(count);
+  let mut pool = page_pool().lock();
+  let pa = pool.allocate(order).map_err(error)?;
+  // Note: pages beyond `count` go back at once
+  for i in count..(1 << order) {
+    pool.free(pa + i * PAGE_SIZE, 0).map_err(error)?;
+  }
+  Ok((0..count).map(|i| PhysicalFrame::new(pa + i * PAGE_SIZE)).collect())
 }
 
 pub fn page_free(pa: usize) -> Result<(), Error> {
   let mut pool = page_pool().lock();
-  pool.free(pa)
-}
\ No newline at end of file
+  pool.free(pa, 0).map_err(error)
+}
+
+pub fn block_free(pa: usize, size: usize) -> Result<(), Error> {
+  let mut pool = page_pool().lock();
+  pool.free(pa, order_of(size / PAGE_SIZE)).map_err(error)
+}
@@ -1,6 +1,7 @@
 use core::fmt::{Display, Formatter};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::arch::{AddressSpaceId, PAGE_SHIFT};
+use crate::arch::{AddressSpaceId, PAGE_SHIFT, PAGE_SIZE};
 use crate::mm::Frame;
 
 pub trait PageTableEntryAttrTrait {
@@ -174,6 +175,7 @@ impl PageTableEntryAttrTrait for EntryAttribute {
 pub struct Entry {
   attribute: EntryAttribute,
   pa: usize,
+  size: usize,
 }
 
 impl Entry {
@@ -181,6 +183,15 @@ impl Entry {
     Entry {
       attribute,
       pa,
+      size: PAGE_SIZE,
+    }
+  }
+  // entry of a mapping covering `size` bytes, see `PageTableTrait::lookup_page`
+  pub fn sized(attribute: EntryAttribute, pa: usize, size: usize) -> Self {
+    Entry {
+      attribute,
+      pa,
+      size,
     }
   }
   pub fn attribute(&self) -> EntryAttribute {
@@ -190,11 +201,58 @@ impl Entry {
     self.pa
   }
   pub fn ppn(&self) -> usize { self.pa >> PAGE_SHIFT }
+  pub fn size(&self) -> usize {
+    self.size
+  }
 }
 
 impl Display for Entry {
   fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
-    write!(f, "PageTableEntry [{:016x}] {:?}", self.pa, self.attribute)
+    write!(f, "PageTableEntry [{:016x}] size {:x} {:?}", self.pa, self.size, self.attribute)
+  }
+}
+
+// pages mapped by `insert_page`, by kind of frame
+#[derive(Debug, Default)]
+pub struct Resident {
+  anonymous: AtomicUsize,
+  shared: AtomicUsize,
+  device: AtomicUsize,
+}
+
+impl Resident {
+  fn counter(&self, frame: &Frame, attr: EntryAttribute) -> &AtomicUsize {
+    match frame {
+      Frame::Device(_) => &self.device,
+      // Note: a copy-on-write page is shared with the address space it was forked from until copied
+      Frame::PhysicalMemory(_) if attr.u_shared() || attr.copy_on_write() => &self.shared,
+      Frame::PhysicalMemory(_) => &self.anonymous,
+    }
+  }
+
+  pub fn insert(&self, frame: &Frame, attr: EntryAttribute) {
+    self.counter(frame, attr).fetch_add(frame.size() / PAGE_SIZE, Ordering::Relaxed);
+  }
+
+  pub fn remove(&self, frame: &Frame, attr: EntryAttribute) {
+    self.counter(frame, attr).fetch_sub(frame.size() / PAGE_SIZE, Ordering::Relaxed);
+  }
+
+  pub fn anonymous(&self) -> usize {
+    self.anonymous.load(Ordering::Relaxed)
+  }
+
+  pub fn shared(&self) -> usize {
+    self.shared.load(Ordering::Relaxed)
+  }
+
+  pub fn device(&self) -> usize {
+    self.device.load(Ordering::Relaxed)
+  }
+
+  // pages backed by memory, device windows left out
+  pub fn memory(&self) -> usize {
+    self.anonymous() + self.shared()
   }
 }
 
@@ -204,11 +262,22 @@ pub trait PageTableTrait {
   fn new(directory: crate::mm::PhysicalFrame) -> Self;
   fn base_pa(&self) -> usize;
   fn map(&self, va: usize, pa: usize, attr: EntryAttribute) -> Result<(), Error>;
+  // map `size` bytes with one entry, a page, a level-2 or a level-1 block, `va` and `pa` aligned to `size`
+  fn map_block(&self, va: usize, pa: usize, size: usize, attr: EntryAttribute) -> Result<(), Error>;
+  // remove the entry mapping `va`, the whole block if it is one
   fn unmap(&self, va: usize);
+  // map `user_frame` as a page or a block of its size, replacing a mapping of the same size only
   fn insert_page(&self, va: usize, user_frame: crate::mm::Frame, attr: EntryAttribute) -> Result<(), Error>;
+  // entry of the page holding `va`, its size is the one of the mapping
   fn lookup_page(&self, va: usize) -> Option<Entry>;
+  // frame of the page or block holding `va`, as inserted by `insert_page`
   fn lookup_user_page(&self, va: usize) -> Option<Frame>;
+  // every page inserted by `insert_page` with its frame
+  fn user_page_list(&self) -> alloc::vec::Vec<(usize, Frame)>;
+  // unmap the page or the whole block holding `va`
   fn remove_page(&self, va: usize) -> Result<(), Error>;
+  fn resident(&self) -> &Resident;
+  fn tlb(&self) -> &crate::mm::tlb::Tlb;
   fn recursive_map(&self, va: usize);
 
   fn install_user_page_table(base: usize, asid: AddressSpaceId);
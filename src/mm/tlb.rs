@@ -0,0 +1,191 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::arch::{AddressSpaceId, PageTable};
+use crate::board::BOARD_CORE_NUMBER;
+use crate::lib::traits::ArchTrait;
+use crate::mm::page_table::PageTableTrait;
+
+// TLB shootdown: entries are invalidated by address and hardware ASID of the page table changed
+// on aarch64 the invalidation is broadcast to every core by the hardware, no core is interrupted
+// on riscv the core changing a page table invalidates its own TLB, interrupts the other cores running the page table
+// and waits for them to invalidate, cores which ran it earlier flush its ASID when they install it again
+// Note: a core in the kernel is not waited for, it flushes on its way back to user,
+//       until then it should not touch user memory of the page table through the TLB
+
+struct CoreState {
+  // base of the user page table installed and its hardware ASID, base 0 when none
+  page_table: AtomicUsize,
+  asid: AtomicUsize,
+  // in an exception taken from user or idle
+  in_kernel: AtomicBool,
+  // flushes asked of the core and flushes it did
+  requested: AtomicUsize,
+  completed: AtomicUsize,
+  // entry to invalidate asked since the last flush, see `post`
+  mailbox: AtomicUsize,
+}
+
+const CORE_STATE: CoreState = CoreState {
+  page_table: AtomicUsize::new(0),
+  asid: AtomicUsize::new(0),
+  in_kernel: AtomicBool::new(false),
+  requested: AtomicUsize::new(0),
+  completed: AtomicUsize::new(0),
+  mailbox: AtomicUsize::new(0),
+};
+
+static CORES: [CoreState; BOARD_CORE_NUMBER] = [CORE_STATE; BOARD_CORE_NUMBER];
+
+// mailbox content: nothing, a whole flush, or a page with the valid bit and the ASID in the top bits
+const MAILBOX_EMPTY: usize = 0;
+const MAILBOX_FLUSH_ALL: usize = usize::MAX;
+const MAILBOX_ASID_SHIFT: usize = 48;
+
+// shootdown state of a page table
+#[derive(Debug, Default)]
+pub struct Tlb {
+  // cores which installed the page table, only they may cache its entries, one bit each
+  cores: AtomicUsize,
+  // cores which skipped a shootdown while running another page table, one bit each
+  stale: AtomicUsize,
+  // hardware ASID the page table was last installed with
+  asid: AtomicUsize,
+}
+
+fn current() -> (usize, &'static CoreState) {
+  let core_id = crate::arch::Arch::core_id();
+  (core_id, &CORES[core_id])
+}
+
+// ask `core` to invalidate page `va` of `asid`, or everything if `va` is none
+// Note: one page is kept, a second request before the flush turns it into a whole flush
+fn post(core: &CoreState, va: Option<usize>, asid: usize) {
+  let entry = match va {
+    Some(va) => (asid << MAILBOX_ASID_SHIFT) | va | 1,
+    None => MAILBOX_FLUSH_ALL,
+  };
+  if core.mailbox.compare_exchange(MAILBOX_EMPTY, entry, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+    core.mailbox.store(MAILBOX_FLUSH_ALL, Ordering::SeqCst);
+  }
+}
+
+fn flush(core: &CoreState) {
+  let requested = core.requested.load(Ordering::SeqCst);
+  if core.completed.load(Ordering::SeqCst) < requested {
+    // Note: empty if an earlier flush took the entry of this request along with its own
+    match core.mailbox.swap(MAILBOX_EMPTY, Ordering::SeqCst) {
+      MAILBOX_EMPTY => {}
+      MAILBOX_FLUSH_ALL => crate::arch::Arch::invalidate_tlb(),
+      entry => {
+        let va = entry & ((1 << MAILBOX_ASID_SHIFT) - 1) & !1;
+        crate::arch::Arch::invalidate_tlb_page(va, entry >> MAILBOX_ASID_SHIFT);
+      }
+    }
+    core.completed.fetch_max(requested, Ordering::SeqCst);
+  }
+}
+
+// current core installed `page_table` tagged with hardware ASID `asid`
+pub fn activate(page_table: &PageTable, asid: AddressSpaceId) {
+  let (core_id, core) = current();
+  core.page_table.store(page_table.base_pa(), Ordering::SeqCst);
+  core.asid.store(asid as usize, Ordering::SeqCst);
+  page_table.tlb().asid.store(asid as usize, Ordering::SeqCst);
+  page_table.tlb().cores.fetch_or(1 << core_id, Ordering::SeqCst);
+  if page_table.tlb().stale.fetch_and(!(1 << core_id), Ordering::SeqCst) & (1 << core_id) != 0 {
+    crate::arch::Arch::invalidate_tlb_asid(asid as usize);
+  }
+}
+
+// invalidate the entry of page `va` of `page_table` in the TLB of every core,
+// returns once none of them may use the old one
+// Note: only needed if the entry was valid, an invalid one is never cached
+pub fn shootdown_page(page_table: &PageTable, va: usize) {
+  invalidate(page_table, Some(va));
+}
+
+// invalidate every entry of `page_table` in the TLB of every core, walks through its tables included
+pub fn shootdown(page_table: &PageTable) {
+  invalidate(page_table, None);
+}
+
+fn invalidate_local(va: Option<usize>, asid: usize) {
+  match va {
+    Some(va) => crate::arch::Arch::invalidate_tlb_page(va, asid),
+    None => crate::arch::Arch::invalidate_tlb_asid(asid),
+  }
+}
+
+fn invalidate(page_table: &PageTable, va: Option<usize>) {
+  let cores = page_table.tlb().cores.load(Ordering::SeqCst);
+  // Note: never installed, no TLB holds its entries
+  if cores == 0 {
+    return;
+  }
+  let base = page_table.base_pa();
+  if crate::arch::Arch::TLB_BROADCAST {
+    let asid = page_table.tlb().asid.load(Ordering::SeqCst);
+    invalidate_local(va, asid);
+    // Note: a core may still run the page table with the ASID of an older generation
+    for core in CORES.iter() {
+      let running = core.asid.load(Ordering::SeqCst);
+      if core.page_table.load(Ordering::SeqCst) == base && running != asid {
+        invalidate_local(va, running);
+      }
+    }
+    return;
+  }
+  let (core_id, current) = current();
+  if current.page_table.load(Ordering::SeqCst) == base {
+    invalidate_local(va, current.asid.load(Ordering::SeqCst));
+  } else if cores & (1 << core_id) != 0 {
+    page_table.tlb().stale.fetch_or(1 << core_id, Ordering::SeqCst);
+  }
+  let mut tickets = [0; BOARD_CORE_NUMBER];
+  for (id, core) in CORES.iter().enumerate() {
+    if id == core_id || cores & (1 << id) == 0 {
+      continue;
+    }
+    if core.page_table.load(Ordering::SeqCst) != base {
+      page_table.tlb().stale.fetch_or(1 << id, Ordering::SeqCst);
+      // Note: the core may have installed the page table before the stale bit was set
+      if core.page_table.load(Ordering::SeqCst) != base {
+        continue;
+      }
+    }
+    post(core, va, core.asid.load(Ordering::SeqCst));
+    let ticket = core.requested.fetch_add(1, Ordering::SeqCst) + 1;
+    if !core.in_kernel.load(Ordering::SeqCst) {
+      crate::driver::ipi::send(id);
+      tickets[id] = ticket;
+    }
+  }
+  for (id, core) in CORES.iter().enumerate() {
+    if tickets[id] == 0 {
+      continue;
+    }
+    while core.completed.load(Ordering::SeqCst) < tickets[id] && !core.in_kernel.load(Ordering::SeqCst) {
+      core::hint::spin_loop();
+    }
+  }
+}
+
+// shootdown interrupt of current core
+pub fn interrupt() {
+  let (_, core) = current();
+  flush(core);
+}
+
+// current core entered the kernel
+pub fn enter() {
+  let (_, core) = current();
+  core.in_kernel.store(true, Ordering::SeqCst);
+}
+
+// current core leaves the kernel, do flushes asked meanwhile
+pub fn exit() {
+  let (_, core) = current();
+  // Note: cleared before looking at requests, a shootdown either sees it or its request is seen here
+  core.in_kernel.store(false, Ordering::SeqCst);
+  flush(core);
+}
@@ -1,6 +1,7 @@
 use rpabi::syscall::error::*;
 
 use crate::lib::thread::Tid;
+use crate::mm::page_table::PageTableTrait;
 
 use super::{Result, SyscallOutRegisters::*};
 
@@ -28,9 +29,51 @@ pub fn address_space_alloc() -> Result {
   Ok(Single(a.asid() as usize))
 }
 
+// Note: only trusted servers may, the id is recycled and pm tells servers keyed by it first
 #[inline(never)]
 pub fn address_space_destroy(asid: u16) -> Result {
+  let current = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  if current.asid() != 1 {
+    return Err(ERROR_DENIED);
+  }
   let a = super::lookup_as(asid)?;
   crate::lib::address_space::address_space_destroy(a);
   Ok(Unit)
 }
+
+// duplicate address space `asid` copy-on-write, the new one has no threads yet
+#[inline(never)]
+pub fn address_space_fork(asid: u16) -> Result {
+  let current = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  let a = super::lookup_as(asid)?;
+  // Note: same rule as `cap_copy`, only trusted address space may duplicate others
+  if a.asid() != current.asid() && current.asid() != 1 {
+    return Err(ERROR_DENIED);
+  }
+  // Note: trusted servers share the address space with kernel objects, never duplicate it
+  if a.asid() == 1 {
+    return Err(ERROR_DENIED);
+  }
+  let child = crate::lib::address_space::address_space_fork(&a)?;
+  Ok(Single(child.asid() as usize))
+}
+
+// limit address space `asid` to `pages` pages of memory, only trusted servers may
+#[inline(never)]
+pub fn address_space_limit(asid: u16, pages: usize) -> Result {
+  let current = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  if current.asid() != 1 {
+    return Err(ERROR_DENIED);
+  }
+  let a = super::lookup_as(asid)?;
+  a.set_limit(pages);
+  Ok(Unit)
+}
+
+// resident pages of address space `asid`: anonymous, shared, device, and its limit
+#[inline(never)]
+pub fn address_space_stat(asid: u16) -> Result {
+  let a = super::lookup_as(asid)?;
+  let resident = a.page_table().resident();
+  Ok(Quadruple(resident.anonymous(), resident.shared(), resident.device(), a.limit()))
+}
@@ -0,0 +1,58 @@
+use rpabi::cap::*;
+use rpabi::syscall::error::*;
+
+use crate::lib::capability::{Capability, CapSlot};
+
+use super::{Result, SyscallOutRegisters::*};
+
+// valid capability held by current address space in `slot`
+pub fn lookup_cap(slot: CapSlot) -> core::result::Result<Capability, super::Error> {
+  let a = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  a.map_caps(|caps| caps.get(slot)).ok_or(ERROR_INVARG)
+}
+
+#[inline(never)]
+pub fn endpoint_create() -> Result {
+  let t = super::current_thread()?;
+  let a = t.address_space().ok_or(ERROR_INTERNAL)?;
+  let cap = Capability::new_endpoint(t.tid());
+  Ok(Single(a.map_caps(|caps| caps.insert(cap))))
+}
+
+#[inline(never)]
+pub fn cap_mint(slot: CapSlot, rights: usize, badge: usize) -> Result {
+  let cap = lookup_cap(slot)?;
+  if !cap.has(CAP_RIGHT_GRANT) || !cap.has(rights & CAP_RIGHT_ALL) {
+    return Err(ERROR_DENIED);
+  }
+  // Note: a badge is set once, `CAP_BADGE_NONE` keeps the current one
+  let badge = match (cap.badge(), badge) {
+    (current, CAP_BADGE_NONE) => current,
+    (CAP_BADGE_NONE, badge) => badge,
+    _ => return Err(ERROR_DENIED),
+  };
+  let a = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  Ok(Single(a.map_caps(|caps| caps.insert(cap.mint(rights, badge)))))
+}
+
+#[inline(never)]
+pub fn cap_copy(slot: CapSlot, asid: u16) -> Result {
+  let cap = lookup_cap(slot)?;
+  if !cap.has(CAP_RIGHT_GRANT) {
+    return Err(ERROR_DENIED);
+  }
+  let current = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  let target = super::lookup_as(asid)?;
+  // Note: only trusted address space may hand capabilities to others
+  if target.asid() != current.asid() && current.asid() != 1 {
+    return Err(ERROR_DENIED);
+  }
+  Ok(Single(target.map_caps(|caps| caps.insert(cap.derive(CAP_RIGHT_ALL)))))
+}
+
+#[inline(never)]
+pub fn cap_revoke(slot: CapSlot) -> Result {
+  let cap = lookup_cap(slot)?;
+  cap.revoke();
+  Ok(Unit)
+}
@@ -0,0 +1,70 @@
+use alloc::vec::Vec;
+
+use rpabi::PAGE_SIZE;
+use rpabi::cap::*;
+use rpabi::channel::CHANNEL_PAGES_MAX;
+use rpabi::syscall::error::*;
+
+use crate::lib::capability::Capability;
+use crate::mm::page_table::{EntryAttribute, PageTableEntryAttrTrait, PageTableTrait};
+
+use super::{Result, SyscallOutRegisters::*};
+
+// map `pages` zeroed pages shared at `va` of current address space and `peer_va` of `peer_asid`,
+// returns the capabilities of two notifications, data and space, installed in each of them
+// Note: the producer signals data and waits for space, the consumer the other way round
+#[inline(never)]
+pub fn channel_create(va: usize, pages: usize, peer_asid: u16, peer_va: usize) -> Result {
+  if pages == 0 || pages > CHANNEL_PAGES_MAX || va % PAGE_SIZE != 0 || peer_va % PAGE_SIZE != 0 {
+    return Err(ERROR_INVARG);
+  }
+  let a = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  let peer = super::lookup_as(peer_asid)?;
+  // Note: same rule as `cap_copy`, only trusted address space may set up a channel with others
+  if peer.asid() != a.asid() && a.asid() != 1 {
+    return Err(ERROR_DENIED);
+  }
+  let size = pages * PAGE_SIZE;
+  let overlap = peer.asid() == a.asid() && va < peer_va + size && peer_va < va + size;
+  if overlap || va.checked_add(size).is_none() || peer_va.checked_add(size).is_none() {
+    return Err(ERROR_INVARG);
+  }
+  // Note: never replace existing mappings
+  for i in 0..pages {
+    let offset = i * PAGE_SIZE;
+    if a.page_table().lookup_user_page(va + offset).is_some()
+      || peer.page_table().lookup_user_page(peer_va + offset).is_some() {
+      return Err(ERROR_INVARG);
+    }
+  }
+  // Note: allocate every frame before mapping any, so that running out of memory leaves no partial channel
+  let mut frames = Vec::with_capacity(pages);
+  for _ in 0..pages {
+    let frame = crate::mm::page_pool::page_alloc().map_err(|_| ERROR_OOM)?;
+    frame.zero();
+    frames.push(crate::mm::Frame::from(frame));
+  }
+  let attr = EntryAttribute::new(true, true, false, false, false, false, true);
+  for (i, uf) in frames.into_iter().enumerate() {
+    let offset = i * PAGE_SIZE;
+    let mapped = a.page_table().insert_page(va + offset, uf.clone(), attr).is_ok();
+    if !mapped || peer.page_table().insert_page(peer_va + offset, uf, attr).is_err() {
+      // Note: unmap what was mapped so far, the frames are freed with their last mapping
+      if mapped {
+        let _ = a.page_table().remove_page(va + offset);
+      }
+      for j in 0..i {
+        let _ = a.page_table().remove_page(va + j * PAGE_SIZE);
+        let _ = peer.page_table().remove_page(peer_va + j * PAGE_SIZE);
+      }
+      return Err(ERROR_INTERNAL);
+    }
+  }
+  let data = Capability::new_notification();
+  let space = Capability::new_notification();
+  let peer_data = peer.map_caps(|caps| caps.insert(data.derive(CAP_RIGHT_SEND | CAP_RIGHT_RECEIVE)));
+  let peer_space = peer.map_caps(|caps| caps.insert(space.derive(CAP_RIGHT_SEND | CAP_RIGHT_RECEIVE)));
+  let data = a.map_caps(|caps| caps.insert(data));
+  let space = a.map_caps(|caps| caps.insert(space));
+  Ok(Quadruple(data, space, peer_data, peer_space))
+}
@@ -2,7 +2,7 @@ use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 
 use rpabi::event::*;
-use rpabi::syscall::error::{ERROR_HOLD_ON, ERROR_INVARG};
+use rpabi::syscall::error::{ERROR_DENIED, ERROR_HOLD_ON, ERROR_INVARG};
 use spin::Mutex;
 
 use crate::lib::interrupt::INT_SEM;
@@ -34,6 +34,20 @@ pub fn event_wait(event_type: usize, event_num: usize) -> Result {
           Err(ERROR_HOLD_ON)
         }
       }
+      Event::DeadlineOverrun(tid) => {
+        // Note: only threads of the same address space or trusted may read and clear the count
+        let target = crate::lib::thread::thread_lookup(tid).ok_or(ERROR_INVARG)?;
+        let current = t.address_space().ok_or(ERROR_INVARG)?;
+        if target.address_space().map_or(true, |a| a != current) && current.asid() != 1 {
+          return Err(ERROR_DENIED);
+        }
+        // Note: returns and clears number of overruns since last wait
+        let mut map = DEADLINE_OVERRUN_MAP.lock();
+        match map.remove(&tid) {
+          Some(count) => Ok(Single(count)),
+          None => Err(ERROR_HOLD_ON),
+        }
+      }
     }
   } else {
     Err(ERROR_INVARG)
@@ -44,6 +58,7 @@ pub fn event_wait(event_type: usize, event_num: usize) -> Result {
 enum Event {
   Interrupt(usize),
   ThreadExit(usize),
+  DeadlineOverrun(usize),
 }
 
 impl Event {
@@ -51,6 +66,7 @@ impl Event {
     match event_type {
       EVENT_INTERRUPT => Some(Event::Interrupt(event_num)),
       EVENT_THREAD_EXIT => Some(Event::ThreadExit(event_num)),
+      EVENT_DEADLINE_OVERRUN => Some(Event::DeadlineOverrun(event_num)),
       _ => None,
     }
   }
@@ -68,3 +84,11 @@ pub fn thread_exit_signal(child_tid: Tid, parent_tid: Tid) {
   }
 }
 
+
+static DEADLINE_OVERRUN_MAP: Mutex<BTreeMap<Tid, usize>> = Mutex::new(BTreeMap::new());
+
+// called when a real-time thread exhausts its budget before finishing its job
+pub fn deadline_overrun_signal(tid: Tid) {
+  let mut map = DEADLINE_OVERRUN_MAP.lock();
+  *map.entry(tid).or_insert(0) += 1;
+}
@@ -1,28 +1,54 @@
 use rpabi::syscall::error::*;
 use crate::lib::cpu::cpu;
 
-use crate::lib::thread::{thread_sleep, thread_sleep_to, Tid};
+use rpabi::itc::ITC_TIMEOUT_INFINITE;
+
+use crate::lib::capability::{CapSlot, Capability};
+use crate::lib::long_message::{self, LongMessage};
+use crate::lib::thread::{ItcMessage, Thread, thread_sleep_to, Tid};
 use crate::lib::thread::Status as ThreadStatus;
 use crate::lib::traits::ContextFrameTrait;
 
 use super::{Result, SyscallOutRegisters::*};
 
 #[inline(never)]
-pub fn itc_receive() -> Result {
+pub fn itc_receive(timeout: usize) -> Result {
   let t = super::current_thread()?;
-  thread_sleep(&t, ThreadStatus::WaitForRequest);
-  Ok(Unit)
+  match t.receive() {
+    Some((sender, badge, message)) => {
+      let (a, b, c, d) = match crate::lib::thread::thread_lookup(sender) {
+        Some(sender) => long_message::deliver(&sender, &t, message),
+        None => message,
+      };
+      Ok(Hexad(sender as usize, a, b, c, d, badge))
+    }
+    None => {
+      crate::lib::timer::ipc_timeout(&t, timeout);
+      cpu().schedule();
+      Ok(Unit)
+    }
+  }
+}
+
+// hand a reply to `target`, a thread suspended on a page fault retries the access instead
+fn reply(current: &Thread, target: &Thread, message: ItcMessage) {
+  long_message::complete(target, current);
+  let (a, b, c, d) = message;
+  if target.take_pager_fault() {
+    target.set_pager_failed(a != 0);
+    return;
+  }
+  target.map_with_context(|ctx| {
+    ctx.set_syscall_result(&Result::Ok(Pentad(current.tid() as usize, a, b, c, d)));
+  });
 }
 
 #[inline(never)]
 pub fn itc_send(tid: Tid, a: usize, b: usize, c: usize, d: usize) -> Result {
   let current = super::current_thread()?;
   let target = crate::lib::thread::thread_lookup(tid).ok_or_else(|| ERROR_INVARG)?;
-  if target.wait_for_reply(|| {
-    target.map_with_context(|ctx| {
-      ctx.set_syscall_result(&Result::Ok(Pentad(current.tid() as usize, a, b, c, d)));
-    });
-  }) {
+  if target.wait_for_reply(current.tid(), || reply(&current, &target, (a, b, c, d))) {
+    current.remove_donor(target.tid());
     Ok(Unit)
   } else {
     Err(ERROR_DENIED)
@@ -30,35 +56,97 @@ pub fn itc_send(tid: Tid, a: usize, b: usize, c: usize, d: usize) -> Result {
 }
 
 #[inline(never)]
-pub fn itc_call(tid: Tid, a: usize, b: usize, c: usize, d: usize) -> Result {
+pub fn itc_call(slot: CapSlot, a: usize, b: usize, c: usize, d: usize, timeout: usize) -> Result {
+  call(slot, (a, b, c, d), None, timeout)
+}
+
+// call passing `[va, va + len)` to the receiver as `c` and `d`, copied or granted as `mode` says
+#[inline(never)]
+pub fn itc_call_long(slot: CapSlot, a: usize, b: usize, va: usize, len: usize, mode: usize) -> Result {
   let current = super::current_thread()?;
-  let target = crate::lib::thread::thread_lookup(tid).ok_or_else(|| ERROR_INVARG)?;
-  if target.wait_for_request(|| {
+  let address_space = current.address_space().ok_or(ERROR_INTERNAL)?;
+  let m = long_message::validate(&address_space, va, len, mode)?;
+  call(slot, (a, b, 0, 0), Some(m), ITC_TIMEOUT_INFINITE)
+}
+
+// suspend current thread on a page fault and send it to the pager behind `cap`, see `rpabi::pager`
+pub fn pager_call(cap: &Capability, message: ItcMessage) -> Result {
+  let current = super::current_thread()?;
+  if !cap.valid() {
+    return Err(ERROR_INVARG);
+  }
+  current.set_pager_fault(true);
+  let r = call_cap(cap, message, None, ITC_TIMEOUT_INFINITE);
+  if r.is_err() {
+    current.set_pager_fault(false);
+  }
+  r
+}
+
+fn call(slot: CapSlot, message: ItcMessage, long: Option<LongMessage>, timeout: usize) -> Result {
+  call_cap(&super::capability::lookup_cap(slot)?, message, long, timeout)
+}
+
+fn call_cap(cap: &Capability, message: ItcMessage, long: Option<LongMessage>, timeout: usize) -> Result {
+  let current = super::current_thread()?;
+  if !cap.has(rpabi::cap::CAP_RIGHT_SEND) {
+    return Err(ERROR_DENIED);
+  }
+  let receiver = cap.receiver().ok_or(ERROR_INVARG)?;
+  let badge = cap.badge();
+  let target = crate::lib::thread::thread_lookup(receiver).ok_or_else(|| ERROR_INVARG)?;
+  if target.tid() == current.tid() {
+    return Err(ERROR_INVARG);
+  }
+  if let Some(m) = &long {
+    if !long_message::fits(&target, m) {
+      return Err(ERROR_INVARG);
+    }
+  }
+  current.map_long_message(|m| *m = long);
+  // Note: only the receiver may reply
+  current.set_replier(target.tid());
+  // Note: block before touching target, so that target may dequeue us as soon as we are queued
+  thread_sleep_to(&current, ThreadStatus::WaitForSend, target.clone());
+  // Note: covers both waiting in the send queue and waiting for reply
+  crate::lib::timer::ipc_timeout(&current, timeout);
+  let enqueued = target.wait_for_request_or_enqueue(|| {
+    let (a, b, c, d) = long_message::deliver(&current, &target, message);
     target.map_with_context(|ctx| {
-      ctx.set_syscall_result(&Result::Ok(Pentad(current.tid() as usize, a, b, c, d)));
+      ctx.set_syscall_result(&Result::Ok(Hexad(current.tid() as usize, a, b, c, d, badge)));
     });
-    thread_sleep_to(&current, crate::lib::thread::Status::WaitForReply, target.clone());
-  }) {
-    cpu().schedule();
-    Ok(Unit)
-  } else {
-    Err(ERROR_HOLD_ON)
+    thread_sleep_to(&current, ThreadStatus::WaitForReply, target.clone());
+  }, &current, badge, message);
+  // Note: target exited after lookup, current is still running and its timed wait is dropped
+  if let Err(e) = enqueued {
+    current.set_timeout(usize::MAX);
+    return Err(e);
   }
+  cpu().schedule();
+  Ok(Unit)
 }
 
 #[inline(never)]
-pub fn itc_reply_recv(tid: Tid, a: usize, b: usize, c: usize, d: usize) -> Result {
+pub fn itc_reply_recv(tid: Tid, a: usize, b: usize, c: usize, d: usize, timeout: usize) -> Result {
   let current = super::current_thread()?;
   let target = crate::lib::thread::thread_lookup(tid).ok_or_else(|| ERROR_INVARG)?;
-  if target.wait_for_reply(|| {
-    target.map_with_context(|ctx| {
-      ctx.set_syscall_result(&Result::Ok(Pentad(current.tid() as usize, a, b, c, d)));
-    });
-    thread_sleep_to(&current, ThreadStatus::WaitForRequest, target.clone());
-  }) {
-    cpu().schedule();
-    Ok(Unit)
+  if target.wait_for_reply(current.tid(), || reply(&current, &target, (a, b, c, d))) {
+    current.remove_donor(target.tid());
+    itc_receive(timeout)
   } else {
     Err(ERROR_DENIED)
   }
 }
+
+// set where long messages to current thread land, see `long_message::receive_buffer`
+#[inline(never)]
+pub fn itc_buffer_set(copy_va: usize, copy_len: usize, window_va: usize, window_len: usize) -> Result {
+  let t = super::current_thread()?;
+  let a = t.address_space().ok_or(ERROR_INTERNAL)?;
+  if copy_len == 0 && window_len == 0 {
+    t.set_receive_buffer(None);
+  } else {
+    t.set_receive_buffer(Some(long_message::receive_buffer(&a, (copy_va, copy_len), (window_va, window_len))?));
+  }
+  Ok(Unit)
+}
@@ -1,23 +1,38 @@
-use rpabi::PAGE_SIZE;
+use rpabi::{CONFIG_USER_LIMIT, PAGE_SIZE, PAGE_TABLE_L1_SHIFT, PAGE_TABLE_L2_SHIFT};
 use rpabi::syscall::error::*;
+use rpabi::vma::*;
 
 use crate::arch::ArchPageTableEntry;
+use crate::lib::address_space::AddressSpace;
 use crate::lib::traits::ArchPageTableEntryTrait;
-use crate::mm::page_table::{Entry, PageTableEntryAttrTrait, PageTableTrait};
+use crate::mm::page_table::{Entry, EntryAttribute, PageTableEntryAttrTrait, PageTableTrait};
 use crate::util::round_down;
 
 use super::{Result, SyscallOutRegisters::*};
 
+// allocate zeroed memory at `va`, a block of `size` if `size` is a block size `va` is aligned to,
+// a page otherwise or if no contiguous memory is free, returns the size mapped
 #[inline(never)]
-pub fn mem_alloc(asid: u16, va: usize, attr: usize) -> Result {
-  let va = round_down(va, PAGE_SIZE);
+pub fn mem_alloc(asid: u16, va: usize, attr: usize, size: usize) -> Result {
   let a = super::lookup_as(asid)?;
+  let attr = Entry::from(ArchPageTableEntry::from_pte(attr)).attribute().filter();
+  let block = (size == 1 << PAGE_TABLE_L2_SHIFT || size == 1 << PAGE_TABLE_L1_SHIFT) && va % size == 0;
+  if block && a.page_table().lookup_page(va).is_none() && a.charge(size / PAGE_SIZE).is_ok() {
+    if let Ok(frame) = crate::mm::page_pool::block_alloc(size) {
+      frame.zero();
+      // Note: falls back to a page if pages are already mapped in the range
+      if a.page_table().insert_page(va, crate::mm::Frame::from(frame), attr).is_ok() {
+        return Ok(Single(size));
+      }
+    }
+  }
+  let va = round_down(va, PAGE_SIZE);
+  a.charge(1)?;
   let frame = crate::mm::page_pool::page_alloc().map_err(|_| ERROR_OOM)?;
   frame.zero();
-  let attr = Entry::from(ArchPageTableEntry::from_pte(attr)).attribute().filter();
   let uf = crate::mm::Frame::from(frame);
   a.page_table().insert_page(va, uf, attr).map_err(|_| ERROR_INTERNAL)?;
-  Ok(Unit)
+  Ok(Single(PAGE_SIZE))
 }
 
 #[inline(never)]
@@ -27,6 +42,8 @@ pub fn mem_map(src_asid: u16, src_va: usize, dst_asid: u16, dst_va: usize, attr:
   let src_as = super::lookup_as(src_asid)?;
   let dst_as = super::lookup_as(dst_asid)?;
   let attr = Entry::from(ArchPageTableEntry::from_pte(attr)).attribute().filter();
+  // Note: a reserved page the source never touched is allocated here
+  let _ = crate::mm::page_fault::populate(&src_as, src_va);
   if let Some(uf) = src_as.page_table().lookup_user_page(src_va) {
     dst_as.page_table().insert_page(dst_va, uf, attr).map_err(|_| ERROR_INTERNAL)?;
     Ok(Unit)
@@ -42,3 +59,153 @@ pub fn mem_unmap(asid: u16, va: usize) -> Result {
   a.page_table().remove_page(va).map_err(|_| ERROR_INTERNAL)?;
   Ok(Unit)
 }
+
+// allocate physically contiguous zeroed `[va, va + len)` for device access, returns its physical address
+// Note: only the trusted address space drives devices
+#[inline(never)]
+pub fn mem_alloc_dma(asid: u16, va: usize, len: usize, attr: usize) -> Result {
+  let (start, end) = vma_range(va, len)?;
+  if super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?.asid() != 1 {
+    return Err(ERROR_DENIED);
+  }
+  let a = super::lookup_as(asid)?;
+  let attr = Entry::from(ArchPageTableEntry::from_pte(attr)).attribute().filter();
+  let pages = (end - start) / PAGE_SIZE;
+  a.charge(pages)?;
+  let frames = crate::mm::page_pool::contiguous_alloc(pages).map_err(|_| ERROR_OOM)?;
+  let pa = dma_address(frames[0].pa());
+  for (i, frame) in frames.into_iter().enumerate() {
+    frame.zero();
+    if a.page_table().insert_page(start + i * PAGE_SIZE, dma_frame(frame), attr).is_err() {
+      // Note: frames not mapped yet are freed on return, mapped ones once unmapped
+      for j in 0..i {
+        let _ = a.page_table().remove_page(start + j * PAGE_SIZE);
+      }
+      return Err(ERROR_INTERNAL);
+    }
+  }
+  Ok(Single(pa))
+}
+
+#[cfg(not(feature = "k210"))]
+fn dma_address(pa: usize) -> usize {
+  pa
+}
+
+#[cfg(not(feature = "k210"))]
+fn dma_frame(frame: crate::mm::PhysicalFrame) -> crate::mm::Frame {
+  crate::mm::Frame::from(frame)
+}
+
+// Note: the k210 DMA controller does not go through the cache, DMA memory is mapped through the uncached alias
+//       of normal memory and stays allocated for good
+#[cfg(feature = "k210")]
+fn dma_address(pa: usize) -> usize {
+  pa - crate::board::BOARD_UNCACHED_MEMORY_OFFSET
+}
+
+#[cfg(feature = "k210")]
+fn dma_frame(frame: crate::mm::PhysicalFrame) -> crate::mm::Frame {
+  let pa = dma_address(frame.pa());
+  core::mem::forget(frame);
+  crate::mm::Frame::Device(pa)
+}
+
+// page aligned, non-empty range below the user limit
+fn vma_range(va: usize, len: usize) -> core::result::Result<(usize, usize), super::Error> {
+  if va % PAGE_SIZE != 0 || len % PAGE_SIZE != 0 || len == 0 {
+    return Err(ERROR_INVARG);
+  }
+  let end = va.checked_add(len).filter(|end| *end <= CONFIG_USER_LIMIT).ok_or(ERROR_INVARG)?;
+  Ok((va, end))
+}
+
+fn vma_attribute(prot: usize) -> core::result::Result<EntryAttribute, super::Error> {
+  if prot & !(VMA_PROT_READ | VMA_PROT_WRITE | VMA_PROT_EXEC) != 0 {
+    return Err(ERROR_INVARG);
+  }
+  Ok(EntryAttribute::new(prot & VMA_PROT_WRITE != 0, prot != 0, false, false, prot & VMA_PROT_EXEC != 0, false, false))
+}
+
+// user pages of `a` mapped in `[start, end)`
+fn mapped_pages(a: &AddressSpace, start: usize, end: usize) -> impl Iterator<Item=(usize, crate::mm::Frame)> {
+  a.page_table().user_page_list().into_iter().filter(move |(va, _)| *va >= start && *va < end)
+}
+
+// address space `asid` if it is the caller's own or the caller is trusted
+// Note: same rule as `cap_copy`, only trusted address space may change the areas of others
+fn lookup_area_owner(asid: u16) -> core::result::Result<AddressSpace, super::Error> {
+  let current = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  let a = super::lookup_as(asid)?;
+  if a.asid() != current.asid() && current.asid() != 1 {
+    return Err(ERROR_DENIED);
+  }
+  Ok(a)
+}
+
+// reserve `[va, va + len)` in address space `asid`, its pages are allocated zeroed on first access
+#[inline(never)]
+pub fn vma_reserve(asid: u16, va: usize, len: usize, prot: usize) -> Result {
+  let (start, end) = vma_range(va, len)?;
+  let attr = vma_attribute(prot)?;
+  let a = lookup_area_owner(asid)?;
+  a.map_vmas(|vmas| vmas.reserve(start, end, attr))?;
+  Ok(Unit)
+}
+
+// change the protection of reserved `[va, va + len)` and the pages already mapped there
+#[inline(never)]
+pub fn vma_protect(asid: u16, va: usize, len: usize, prot: usize) -> Result {
+  let (start, end) = vma_range(va, len)?;
+  let attr = vma_attribute(prot)?;
+  let a = lookup_area_owner(asid)?;
+  a.map_vmas(|vmas| {
+    vmas.protect(start, end, attr)?;
+    for (va, frame) in mapped_pages(&a, start, end) {
+      let current = match a.page_table().lookup_page(va) {
+        Some(entry) => entry.attribute(),
+        None => continue,
+      };
+      // Note: a copy-on-write page stays read-only, the write fault copies it if the area allows writes,
+      //       so does a private page another address space maps since a fork
+      let shared = matches!(&frame, crate::mm::Frame::PhysicalMemory(f) if alloc::sync::Arc::strong_count(f) > 2);
+      let cow = current.copy_on_write() || (attr.writable() && shared && !current.u_shared());
+      let attr = EntryAttribute::new(attr.writable() && !cow, attr.u_readable(), current.device(), false,
+                                     attr.u_executable(), cow, current.u_shared());
+      a.page_table().insert_page(va, frame, attr).map_err(|_| ERROR_INTERNAL)?;
+    }
+    Ok(())
+  })?;
+  Ok(Unit)
+}
+
+// drop reserved `[va, va + len)` and unmap its pages
+#[inline(never)]
+pub fn vma_release(asid: u16, va: usize, len: usize) -> Result {
+  let (start, end) = vma_range(va, len)?;
+  let a = lookup_area_owner(asid)?;
+  a.map_vmas(|vmas| {
+    vmas.release(start, end)?;
+    for (va, _) in mapped_pages(&a, start, end) {
+      let _ = a.page_table().remove_page(va);
+    }
+    Ok(())
+  })?;
+  Ok(Unit)
+}
+
+// send faults on reserved `[va, va + len)` to the endpoint in `slot` of current address space
+#[inline(never)]
+pub fn vma_pager(asid: u16, va: usize, len: usize, slot: usize) -> Result {
+  let (start, end) = vma_range(va, len)?;
+  let pager = super::capability::lookup_cap(slot)?;
+  if pager.receiver().is_none() {
+    return Err(ERROR_INVARG);
+  }
+  if !pager.has(rpabi::cap::CAP_RIGHT_SEND) {
+    return Err(ERROR_DENIED);
+  }
+  let a = lookup_area_owner(asid)?;
+  a.map_vmas(|vmas| vmas.set_pager(start, end, pager))?;
+  Ok(Unit)
+}
@@ -15,6 +15,9 @@ pub mod address_space;
 pub mod event;
 pub mod ipc;
 pub mod server;
+pub mod capability;
+pub mod notification;
+pub mod channel;
 
 pub type Error = usize;
 
@@ -32,6 +35,8 @@ pub enum SyscallOutRegisters {
   Quadruple(usize, usize, usize, usize),
   #[allow(dead_code)]
   Pentad(usize, usize, usize, usize, usize),
+  #[allow(dead_code)]
+  Hexad(usize, usize, usize, usize, usize, usize),
 }
 
 impl Display for SyscallOutRegisters {
@@ -43,6 +48,7 @@ impl Display for SyscallOutRegisters {
       Triple(a, b, c) => { write!(f, "({:x}, {:x}, {:x})", a, b, c)? }
       Quadruple(a, b, c, d) => { write!(f, "({:x}, {:x}, {:x}, {:x})", a, b, c, d)? }
       Pentad(a, b, c, d, e) => { write!(f, "({:x}, {:x}, {:x}, {:x}, {:x})", a, b, c, d, e)? }
+      Hexad(a, b, c, d, e, g) => { write!(f, "({:x}, {:x}, {:x}, {:x}, {:x}, {:x})", a, b, c, d, e, g)? }
     }
     Ok(())
   }
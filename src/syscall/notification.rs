@@ -0,0 +1,64 @@
+use rpabi::cap::*;
+use rpabi::syscall::error::*;
+
+use crate::lib::capability::{Capability, CapSlot};
+use crate::lib::cpu::cpu;
+use crate::lib::notification::Notification;
+
+use super::{Result, SyscallOutRegisters::*};
+
+fn lookup_notification(slot: CapSlot, rights: usize) -> core::result::Result<Notification, super::Error> {
+  let cap = super::capability::lookup_cap(slot)?;
+  let n = cap.notification().ok_or(ERROR_INVARG)?;
+  if !cap.has(rights) {
+    return Err(ERROR_DENIED);
+  }
+  Ok(n)
+}
+
+#[inline(never)]
+pub fn notification_create() -> Result {
+  let a = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  let cap = Capability::new_notification();
+  Ok(Single(a.map_caps(|caps| caps.insert(cap))))
+}
+
+#[inline(never)]
+pub fn notification_signal(slot: CapSlot, bits: usize) -> Result {
+  if bits == 0 {
+    return Err(ERROR_INVARG);
+  }
+  lookup_notification(slot, CAP_RIGHT_SEND)?.signal(bits);
+  Ok(Unit)
+}
+
+#[inline(never)]
+pub fn notification_wait(slot: CapSlot) -> Result {
+  let n = lookup_notification(slot, CAP_RIGHT_RECEIVE)?;
+  let t = super::current_thread()?;
+  match t.wait_for_notification(&n) {
+    Some(word) => Ok(Single(word)),
+    None => {
+      // Note: result is set by the signalling thread
+      cpu().schedule();
+      Ok(Unit)
+    }
+  }
+}
+
+#[inline(never)]
+pub fn notification_poll(slot: CapSlot) -> Result {
+  let n = lookup_notification(slot, CAP_RIGHT_RECEIVE)?;
+  Ok(Single(n.take()))
+}
+
+#[inline(never)]
+pub fn notification_bind(slot: CapSlot) -> Result {
+  let n = lookup_notification(slot, CAP_RIGHT_RECEIVE)?;
+  let t = super::current_thread()?;
+  if !n.bind(t.tid()) {
+    return Err(ERROR_DENIED);
+  }
+  t.bind_notification(n);
+  Ok(Unit)
+}
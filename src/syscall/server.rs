@@ -1,43 +1,181 @@
 use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
 
-use rpabi::syscall::error::ERROR_INVARG;
+use rpabi::cap::*;
+use rpabi::server::SERVICE_NAME_MAX;
+use rpabi::syscall::error::*;
 use spin::Mutex;
 
-use crate::lib::thread::Tid;
+use crate::lib::address_space::AddressSpace;
+use crate::lib::capability::{CapSlot, Capability};
+use crate::lib::cpu::cpu;
+use crate::lib::thread::{thread_lookup, thread_sleep_to, thread_wake, Status, Tid};
+use crate::lib::traits::ContextFrameTrait;
 
 use super::{Result, SyscallOutRegisters::*};
 
-#[inline(never)]
-pub fn server_register(server_id: usize) -> Result {
+// Note: fixed ids of `rpabi::server` and names share one registry
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+enum ServiceKey {
+  Id(usize),
+  Name(String),
+}
+
+struct Service {
+  // send capability every client capability derives from
+  cap: Capability,
+  // thread which registered the service, removed when it exits
+  owner: Tid,
+}
+
+struct Registry {
+  services: BTreeMap<ServiceKey, Service>,
+  // threads blocked in `service_lookup` until the name is registered
+  waiters: Vec<(ServiceKey, Tid)>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+  services: BTreeMap::new(),
+  waiters: Vec::new(),
+});
+
+// endpoint capability in `slot` of current address space which may be published
+fn lookup_endpoint(slot: CapSlot) -> core::result::Result<Capability, super::Error> {
+  let cap = super::capability::lookup_cap(slot)?;
+  if cap.receiver().is_none() {
+    return Err(ERROR_INVARG);
+  }
+  if !cap.has(CAP_RIGHT_GRANT) {
+    return Err(ERROR_DENIED);
+  }
+  Ok(cap)
+}
+
+fn read_name(va: usize, len: usize) -> core::result::Result<String, super::Error> {
+  if len == 0 || len > SERVICE_NAME_MAX {
+    return Err(ERROR_INVARG);
+  }
+  let a = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  let mut buf = [0u8; SERVICE_NAME_MAX];
+  a.read_user(va, &mut buf[..len])?;
+  let name = core::str::from_utf8(&buf[..len]).map_err(|_| ERROR_INVARG)?;
+  Ok(String::from(name))
+}
+
+// install a send capability to the service in `a`, badged with its asid
+fn install(cap: &Capability, a: &AddressSpace) -> CapSlot {
+  let badge = a.asid() as usize;
+  a.map_caps(|caps| {
+    match caps.find(cap, CAP_RIGHT_SEND, badge) {
+      Some(slot) => slot,
+      None => caps.insert(cap.mint(CAP_RIGHT_SEND, badge)),
+    }
+  })
+}
+
+fn register(key: ServiceKey, endpoint: Capability) -> Result {
+  let owner = super::current_thread()?.tid();
+  let mut registry = REGISTRY.lock();
+  // Note: only the owner may replace a service, an exited owner has already been removed
+  if let Some(service) = registry.services.get(&key) {
+    if service.owner != owner {
+      return Err(ERROR_DENIED);
+    }
+    service.cap.revoke();
+  }
+  let cap = endpoint.derive(CAP_RIGHT_SEND);
+  let (woken, waiting): (Vec<_>, Vec<_>) = registry.waiters.drain(..).partition(|(k, _)| *k == key);
+  registry.waiters = waiting;
+  for (_, tid) in woken.into_iter() {
+    if let Some(t) = thread_lookup(tid) {
+      if let Some(a) = t.address_space() {
+        let slot = install(&cap, &a);
+        t.map_with_context(|ctx| ctx.set_syscall_result(&Ok(Single(slot))));
+        thread_wake(&t);
+      }
+    }
+  }
+  registry.services.insert(key, Service { cap, owner });
+  Ok(Unit)
+}
+
+fn lookup(key: ServiceKey, wait: bool) -> Result {
   let t = super::current_thread()?;
-  set(server_id, t.tid());
+  let a = t.address_space().ok_or(ERROR_INTERNAL)?;
+  let mut registry = REGISTRY.lock();
+  if let Some(service) = registry.services.get(&key).filter(|s| s.cap.valid()) {
+    return Ok(Single(install(&service.cap, &a)));
+  }
+  if !wait {
+    return Err(ERROR_INVARG);
+  }
+  // Note: sleep before releasing the registry, so that `register` cannot miss us
+  registry.waiters.push((key, t.tid()));
+  thread_sleep_to(&t, Status::Sleep, t.clone());
+  drop(registry);
+  cpu().schedule();
   Ok(Unit)
 }
 
-#[inline(never)]
-pub fn server_tid(server_id: usize) -> Result {
-  match get(server_id) {
-    None => {
-      Err(ERROR_INVARG)
+fn unregister(key: ServiceKey) -> Result {
+  let owner = super::current_thread()?.tid();
+  let mut registry = REGISTRY.lock();
+  match registry.services.get(&key) {
+    Some(service) if service.owner == owner => {
+      service.cap.revoke();
+      registry.services.remove(&key);
+      Ok(Unit)
     }
-    Some(tid) => {
-      Ok(Single(tid as usize))
+    Some(_) => Err(ERROR_DENIED),
+    None => Err(ERROR_INVARG),
+  }
+}
+
+// drop services registered by an exiting thread, capabilities clients got from them stop working
+pub fn service_exit(tid: Tid) {
+  let mut registry = REGISTRY.lock();
+  registry.services.retain(|_, service| {
+    if service.owner == tid {
+      service.cap.revoke();
+      false
+    } else {
+      true
     }
+  });
+  registry.waiters.retain(|(_, waiter)| *waiter != tid);
+}
+
+#[inline(never)]
+pub fn server_register(server_id: usize, slot: usize) -> Result {
+  let a = super::current_thread()?.address_space().ok_or(ERROR_INTERNAL)?;
+  // Note: only trusted servers are published, so that no user program can impersonate one
+  if a.asid() != 1 {
+    return Err(ERROR_DENIED);
   }
+  register(ServiceKey::Id(server_id), lookup_endpoint(slot)?)
+}
+
+// install a send capability to the server in current address space, badged with its asid
+#[inline(never)]
+pub fn server_cap(server_id: usize) -> Result {
+  lookup(ServiceKey::Id(server_id), false)
 }
 
-static SERVER_MAP: Mutex<BTreeMap<usize, Tid>> = Mutex::new(BTreeMap::new());
+// publish the endpoint in `slot` under a name, owned by current thread
+#[inline(never)]
+pub fn service_register(name: usize, len: usize, slot: usize) -> Result {
+  let endpoint = lookup_endpoint(slot)?;
+  register(ServiceKey::Name(read_name(name, len)?), endpoint)
+}
 
-fn get(server_id: usize) -> Option<Tid> {
-  let map = SERVER_MAP.lock();
-  map.get(&server_id).map(|x| *x)
+// like `server_cap`, blocks until the name is registered if `wait` is set
+#[inline(never)]
+pub fn service_lookup(name: usize, len: usize, wait: usize) -> Result {
+  lookup(ServiceKey::Name(read_name(name, len)?), wait != 0)
 }
 
-fn set(server_id: usize, tid: Tid) {
-  let mut map = SERVER_MAP.lock();
-  if let Some(t) = map.get_mut(&server_id) {
-    *t = tid;
-  } else {
-    map.insert(server_id, tid);
-  }
+#[inline(never)]
+pub fn service_unregister(name: usize, len: usize) -> Result {
+  unregister(ServiceKey::Name(read_name(name, len)?))
 }
@@ -1,6 +1,7 @@
 use rpabi::syscall::error::{ERROR_DENIED, ERROR_INVARG};
 
-use crate::lib::thread::{thread_sleep, thread_wake, Tid};
+use crate::lib::thread::{Thread, thread_sleep, thread_wake, Tid};
+use crate::lib::traits::ArchTrait;
 
 use super::{Result, SyscallOutRegisters::*};
 
@@ -11,6 +12,12 @@ pub fn get_tid() -> Result {
 
 #[inline(never)]
 pub fn thread_yield() -> Result {
+  // Note: a runnable real-time thread yielding has finished its current job
+  if let Ok(t) = super::current_thread() {
+    if t.is_realtime() && t.runnable() {
+      crate::lib::realtime::complete(&t);
+    }
+  }
   // let icntr = crate::lib::timer::current_cycle();
   crate::lib::cpu::cpu().schedule();
   // let icntr2 = crate::lib::timer::current_cycle();
@@ -61,7 +68,7 @@ pub fn thread_set_status(tid: usize, status: usize) -> Result {
       if runnable {
         thread_wake(&t);
       } else {
-        thread_sleep(&t, crate::lib::thread::Status::Sleep);
+        thread_sleep(&t, crate::lib::thread::Status::Suspended);
       }
       Ok(Unit)
     }
@@ -71,9 +78,114 @@ pub fn thread_set_status(tid: usize, status: usize) -> Result {
 #[inline(never)]
 pub fn yield_to(tid: usize) -> Result {
   if let Some(t) = crate::lib::thread::thread_lookup(tid) {
+    if !t.allowed_on(crate::arch::Arch::core_id()) {
+      return Err(ERROR_DENIED);
+    }
     crate::lib::cpu::cpu().schedule_to(t);
     Ok(Unit)
   } else {
     Err(ERROR_INVARG)
   }
 }
+
+fn lookup_self_or_child(current_thread: &Thread, tid: Tid) -> core::result::Result<Thread, super::Error> {
+  if tid == 0 {
+    return Ok(current_thread.clone());
+  }
+  match crate::lib::thread::thread_lookup(tid) {
+    None => Err(ERROR_INVARG),
+    Some(t) => {
+      if t.tid() == current_thread.tid() || t.is_child_of(current_thread.tid()) {
+        Ok(t)
+      } else {
+        Err(ERROR_DENIED)
+      }
+    }
+  }
+}
+
+#[inline(never)]
+pub fn thread_set_priority(tid: usize, priority: usize) -> Result {
+  use rpabi::thread::*;
+  if priority > THREAD_PRIORITY_MAX {
+    return Err(ERROR_INVARG);
+  }
+  let current_thread = super::current_thread()?;
+  let t = lookup_self_or_child(&current_thread, tid)?;
+  // Note: only trusted threads may raise a priority above their own, inherited priority does not count
+  let trusted = current_thread.address_space().map_or(false, |a| a.asid() == 1);
+  if priority > current_thread.base_priority() && !trusted {
+    return Err(ERROR_DENIED);
+  }
+  crate::lib::thread::thread_set_priority(&t, priority);
+  Ok(Unit)
+}
+
+#[inline(never)]
+pub fn thread_set_affinity(tid: usize, mask: usize) -> Result {
+  use crate::board::BOARD_CORE_NUMBER;
+  let mask = mask & ((1 << BOARD_CORE_NUMBER) - 1);
+  if mask == 0 {
+    return Err(ERROR_INVARG);
+  }
+  let current_thread = super::current_thread()?;
+  let t = lookup_self_or_child(&current_thread, tid)?;
+  crate::lib::thread::thread_set_affinity(&t, mask);
+  Ok(Unit)
+}
+
+#[inline(never)]
+pub fn thread_set_realtime(tid: usize, period: usize, budget: usize, deadline: usize) -> Result {
+  use crate::lib::timer::us_to_count;
+  let current_thread = super::current_thread()?;
+  let t = lookup_self_or_child(&current_thread, tid)?;
+  if period == 0 {
+    // Note: only the thread itself may leave real-time class, it is running and not queued anywhere
+    if t.tid() != current_thread.tid() {
+      return Err(ERROR_DENIED);
+    }
+    crate::lib::realtime::detach(&t);
+    return Ok(Unit);
+  }
+  crate::lib::realtime::admit(&t, us_to_count(period), us_to_count(budget), us_to_count(deadline))?;
+  Ok(Unit)
+}
+
+#[inline(never)]
+pub fn sleep(us: usize) -> Result {
+  if us == 0 {
+    return thread_yield();
+  }
+  let t = super::current_thread()?;
+  let deadline = crate::driver::timer::counter().saturating_add(crate::lib::timer::us_to_count(us));
+  crate::lib::timer::sleep_until(&t, deadline);
+  Ok(Unit)
+}
+
+#[inline(never)]
+pub fn sleep_until(us: usize) -> Result {
+  let t = super::current_thread()?;
+  let deadline = crate::lib::timer::us_to_count(us);
+  if deadline <= crate::driver::timer::counter() {
+    return Ok(Unit);
+  }
+  crate::lib::timer::sleep_until(&t, deadline);
+  Ok(Unit)
+}
+
+#[inline(never)]
+pub fn thread_stat(tid: usize) -> Result {
+  let current_thread = super::current_thread()?;
+  let t = if tid == 0 {
+    current_thread.clone()
+  } else {
+    crate::lib::thread::thread_lookup(tid).ok_or(ERROR_INVARG)?
+  };
+  // Note: threads of other address spaces are only visible to trusted
+  let current = current_thread.address_space().ok_or(ERROR_INVARG)?;
+  if t.address_space().map_or(true, |a| a != current) && current.asid() != 1 {
+    return Err(ERROR_DENIED);
+  }
+  let as_cycles = t.address_space().map_or(0, |a| a.cycles());
+  Ok(Quadruple(t.cycles(), t.switches(), t.last_core(), as_cycles))
+}
@@ -4,6 +4,7 @@ use core::convert::TryInto;
 use k210_hal::pac::SPI0;
 
 use crate::blk::k210::soc::dmac::DMACExt;
+use crate::libtrusted::mm::dma_alloc;
 use crate::blk::k210::soc::spi::{SPIExt, SPIImpl};
 
 use super::super::soc::dmac::{dma_channel, DMAC};
@@ -17,6 +18,8 @@ pub struct SDCard<'a, SPI> {
   cs_gpionum: u8,
   dmac: &'a DMAC,
   channel: dma_channel,
+  // one sector of words moved by the DMA controller
+  dma: usize,
 }
 
 /*
@@ -202,6 +205,7 @@ impl<'a, X: SPI> SDCard<'a, X> {
       cs_gpionum,
       dmac,
       channel,
+      dma: dma_alloc(1).expect("sdcard dma alloc failed").0,
     }
   }
 
@@ -634,8 +638,7 @@ impl<'a, X: SPI> SDCard<'a, X> {
       return Err(());
     }
     let mut error = false;
-    let dma_frame: usize = 0x8_0000_0000;
-    let dma_chunk = unsafe { core::slice::from_raw_parts_mut(dma_frame as *mut u32, SEC_LEN) };
+    let dma_chunk = unsafe { core::slice::from_raw_parts_mut(self.dma as *mut u32, SEC_LEN) };
     for chunk in data_buf.chunks_mut(SEC_LEN) {
       if self.get_response() != SD_START_DATA_SINGLE_BLOCK_READ {
         error = true;
@@ -696,8 +699,7 @@ impl<'a, X: SPI> SDCard<'a, X> {
       self.end_cmd();
       return Err(());
     }
-    let dma_frame: usize = 0x8_0000_0000;
-    let dma_chunk = unsafe { core::slice::from_raw_parts_mut(dma_frame as *mut u32, SEC_LEN) };
+    let dma_chunk = unsafe { core::slice::from_raw_parts_mut(self.dma as *mut u32, SEC_LEN) };
     for chunk in data_buf.chunks(SEC_LEN) {
       /* Send the data token to signify the start of the data */
       self.write_data(&frame);
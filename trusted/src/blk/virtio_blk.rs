@@ -1,4 +1,5 @@
 use alloc::boxed::Box;
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 use core::mem::size_of;
 
@@ -7,7 +8,9 @@ use tock_registers::*;
 use tock_registers::interfaces::{Readable, Writeable};
 use tock_registers::registers::*;
 
-use crate::libtrusted::mm::virt_to_phys;
+use rpabi::PAGE_SIZE;
+
+use crate::libtrusted::mm::{dma_alloc, virt_to_phys};
 use rpsyscall::get_tid;
 
 #[cfg(target_arch = "aarch64")]
@@ -159,7 +162,7 @@ fn setup_queue(idx: u32) {
   }
   mmio.QueueNum.set(QUEUE_SIZE as u32);
 
-  let ring = VIRTIO_RING.lock();
+  let ring = ring().lock();
 
   mmio.QueueDescLow.set(virt_to_phys(ring.desc.base_addr_usize()) as u32);
   mmio.QueueDescHigh.set((virt_to_phys(ring.desc.base_addr_usize()) >> 32) as u32);
@@ -226,27 +229,15 @@ struct VirtioRing {
   device: VirtioRingDevice,
 }
 
-static VIRTIO_RING: Mutex<VirtioRing> = Mutex::new(VirtioRing {
-  desc: [VirtioRingDesc {
-    addr: 0,
-    len: 0,
-    flags: 0,
-    next: 0,
-  }; QUEUE_SIZE],
-  driver: VirtioRingDriver {
-    flags: 0,
-    idx: 0,
-    ring: [0; QUEUE_SIZE],
-  },
-  device: VirtioRingDevice {
-    flags: 0,
-    idx: 0,
-    ring: [VirtioRingDeviceElement {
-      id: 0,
-      len: 0,
-    }; QUEUE_SIZE],
-  },
-});
+// Note: zeroed pages from `dma_alloc`, the device reaches each part of the ring at its physical address
+static VIRTIO_RING: Once<Mutex<&'static mut VirtioRing>> = Once::new();
+
+fn ring() -> &'static Mutex<&'static mut VirtioRing> {
+  VIRTIO_RING.call_once(|| {
+    let (va, _) = dma_alloc(size_of::<VirtioRing>() / PAGE_SIZE).expect("virtio ring dma alloc failed");
+    Mutex::new(unsafe { &mut *(va as *mut VirtioRing) })
+  })
+}
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -330,7 +321,7 @@ fn io(sector: usize, count: usize, buf: usize, op: Operation, src: usize) {
     sector: sector as u64,
   });
   let status = Box::new(255u8);
-  let mut ring = VIRTIO_RING.lock();
+  let mut ring = ring().lock();
 
   let desc = ring.desc.get_mut(0).unwrap();
   desc.addr = virt_to_phys(hdr.as_ref() as *const VirtioBlkOutHdr as usize) as u64;
@@ -382,7 +373,7 @@ fn irq() {
   if status & 0b01 != 0 {
     // Used Buffer Notification: the device has used a buffer in at least one of the active virtual queues.
 
-    let ring = VIRTIO_RING.lock();
+    let ring = ring().lock();
     let mut disk = DISK.lock();
     let used = &ring.device;
 
@@ -438,18 +429,39 @@ fn wait_for_irq() {
 pub fn server() {
   init();
   info!("server started t{}",  get_tid());
+  let irq_notification = rpsyscall::notification_create().unwrap();
+  rpsyscall::notification_bind(irq_notification).unwrap();
+  // Note: interrupts are forwarded by another thread, so that requests can be received while io is in flight
+  let forwarder = crate::libtrusted::thread::spawn(move || {
+    loop {
+      wait_for_irq();
+      let _ = rpsyscall::notification_signal(irq_notification, 1);
+    }
+  });
+  rpsyscall::thread_set_priority(forwarder.native(), rpabi::thread::THREAD_PRIORITY_DRIVER).unwrap();
+  // Note: kernel routes device interrupts to core 0
+  rpsyscall::thread_set_affinity(forwarder.native(), 1 << 0).unwrap();
   rpsyscall::server_register(rpabi::server::SERVER_BLK).unwrap();
 
+  // requests waiting for the in-flight one, only descriptor chain #0 is used
+  let mut pending = VecDeque::new();
   loop {
     let (client_tid, msg) = rpsyscall::message::Message::receive().unwrap();
-    if msg.d == rpservapi::blk::action::READ || msg.d == rpservapi::blk::action::WRITE {
+    if client_tid == rpabi::cap::CAP_NOTIFICATION_SENDER {
+      irq();
+      if let Some((sector, count, buf, op, src)) = pending.pop_front() {
+        io(sector, count, buf, op, src);
+      }
+    } else if msg.d == rpservapi::blk::action::READ || msg.d == rpservapi::blk::action::WRITE {
       let sector = msg.a;
       let count = msg.b;
       let buf = msg.c;
       let op = if msg.d == rpservapi::blk::action::READ { Operation::Read } else { Operation::Write };
-      io(sector, count, buf, op, client_tid);
-      wait_for_irq();
-      irq();
+      if DISK.lock().requests.is_empty() {
+        io(sector, count, buf, op, client_tid);
+      } else {
+        pending.push_back((sector, count, buf, op, client_tid));
+      }
     } else if msg.d == rpservapi::blk::action::SIZE {
       let mut msg = rpsyscall::message::Message::default();
       msg.a = match VIRTIO_MMIO.disk_size.get() {
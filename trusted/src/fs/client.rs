@@ -1,5 +1,6 @@
 // TODO: merge it with fs.rs in rpstdlib
 
+use rpabi::itc::{ITC_LONG_MAX, ITC_LONG_WRITE};
 use rpabi::server::SERVER_REDOX_FS;
 
 use rpsyscall::message::Message;
@@ -43,14 +44,23 @@ impl File {
   }
 
   pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-    let msg = Message {
-      a: SYS_READ,
-      b: self.handle,
-      c: buf.as_ptr() as usize,
-      d: buf.len(),
-    };
-    let msg = msg.call(SERVER_REDOX_FS).map_err(|_| Error::new(EIO))?;
-    Error::demux(msg.a)
+    let mut done = 0;
+    // Note: the buffer is passed as a long message, the kernel copies the reply back into it
+    for chunk in buf.chunks_mut(ITC_LONG_MAX) {
+      let msg = Message::new(SYS_READ, self.handle, 0, 0);
+      let msg = msg.call_long(SERVER_REDOX_FS, chunk.as_mut_ptr() as usize, chunk.len(), ITC_LONG_WRITE)
+        .map_err(|_| Error::new(EIO))?;
+      let n = match Error::demux(msg.a) {
+        Ok(n) => n,
+        Err(e) if done == 0 => return Err(e),
+        Err(_) => break,
+      };
+      done += n;
+      if n < chunk.len() {
+        break;
+      }
+    }
+    Ok(done)
   }
 
   pub fn write(&mut self, buf: &[u8]) -> Result<usize> {
@@ -1,70 +1,51 @@
 use rpabi::PAGE_SIZE;
 
-use crate::libtrusted::mm::{virtual_alloc, virtual_free};
+use crate::libtrusted::mm::dma_alloc;
 use rpsyscall::message::Message;
 use redox::*;
 
 use crate::fs::{BLOCK_SIZE, Disk};
 
-pub struct VirtioClient;
+// pages moved by one request through the bounce buffer
+const DMA_PAGES: usize = 16;
+
+pub struct VirtioClient {
+  // physically contiguous, so that one request may span several pages
+  dma: usize,
+}
 
 impl VirtioClient {
   pub fn new() -> VirtioClient {
-    VirtioClient
+    let (dma, _) = dma_alloc(DMA_PAGES).expect("virtio client dma alloc failed");
+    VirtioClient { dma }
   }
 
-  fn read_block_unaligned(&self, block: u64, buffer: &mut [u8]) -> Result<usize> {
-    assert_eq!(buffer.len(), PAGE_SIZE);
-    let va_tmp = virtual_alloc(1, true).unwrap();
-    let aligned_buffer = unsafe { core::slice::from_raw_parts_mut(va_tmp as *mut u8, PAGE_SIZE) };
-    let read = self.read_block_aligned(block, aligned_buffer)?;
-    for i in 0..PAGE_SIZE {
-      buffer[i] = aligned_buffer[i];
-    }
-    virtual_free(va_tmp, 1);
-    Ok(read)
-  }
-
-  fn read_block_aligned(&self, block: u64, buffer: &mut [u8]) -> Result<usize> {
-    assert_eq!(buffer.len(), PAGE_SIZE);
+  fn request(&self, block: u64, pages: usize, action: usize) -> Result<()> {
     let msg = Message {
       a: (block as usize) * 8,
-      b: 8,
-      c: buffer.as_mut_ptr() as usize,
-      d: rpservapi::blk::action::READ,
+      b: 8 * pages,
+      c: self.dma,
+      d: action,
     }.call(rpabi::server::SERVER_BLK).map_err(|_| Error::new(EIO))?;
     if msg.a == rpservapi::blk::result::OK {
-      Ok(buffer.len())
+      Ok(())
     } else {
       Err(Error::new(EIO))
     }
   }
 
-  fn write_block_unaligned(&self, block: u64, buffer: &[u8]) -> Result<usize> {
-    assert_eq!(buffer.len(), PAGE_SIZE);
-    let va_tmp = virtual_alloc(1, true).unwrap();
-    let aligned_buffer = unsafe { core::slice::from_raw_parts_mut(va_tmp as *mut u8, PAGE_SIZE) };
-    for i in 0..PAGE_SIZE {
-      aligned_buffer[i] = buffer[i];
-    }
-    let read = self.write_block_aligned(block, aligned_buffer)?;
-    virtual_free(va_tmp, 1);
-    Ok(read)
+  fn read_pages(&self, block: u64, buffer: &mut [u8]) -> Result<usize> {
+    self.request(block, buffer.len() / PAGE_SIZE, rpservapi::blk::action::READ)?;
+    let dma = unsafe { core::slice::from_raw_parts(self.dma as *const u8, buffer.len()) };
+    buffer.copy_from_slice(dma);
+    Ok(buffer.len())
   }
 
-  fn write_block_aligned(&self, block: u64, buffer: &[u8]) -> Result<usize> {
-    assert_eq!(buffer.len(), PAGE_SIZE);
-    let msg = Message {
-      a: (block as usize) * 8,
-      b: 8,
-      c: buffer.as_ptr() as usize,
-      d: rpservapi::blk::action::WRITE,
-    }.call(rpabi::server::SERVER_BLK).map_err(|_| Error::new(EIO))?;
-    if msg.a == rpservapi::blk::result::OK {
-      Ok(buffer.len())
-    } else {
-      Err(Error::new(EIO))
-    }
+  fn write_pages(&self, block: u64, buffer: &[u8]) -> Result<usize> {
+    let dma = unsafe { core::slice::from_raw_parts_mut(self.dma as *mut u8, buffer.len()) };
+    dma.copy_from_slice(buffer);
+    self.request(block, buffer.len() / PAGE_SIZE, rpservapi::blk::action::WRITE)?;
+    Ok(buffer.len())
   }
 }
 
@@ -72,28 +53,18 @@ impl Disk for VirtioClient {
   fn read_at(&mut self, block: u64, buffer: &mut [u8]) -> Result<usize> {
     // trace!("start {:016x} len {:016x}", buffer.as_ptr() as usize, buffer.len());
     assert_eq!(buffer.len() % BLOCK_SIZE as usize, 0);
-    let page_num = buffer.len() / PAGE_SIZE;
     let mut sum = 0;
-    for i in 0..page_num {
-      if buffer.as_ptr() as usize % PAGE_SIZE == 0 {
-        sum += self.read_block_aligned(block + (i as u64), &mut buffer[(PAGE_SIZE * i)..(PAGE_SIZE * (i + 1))])?;
-      } else {
-        sum += self.read_block_unaligned(block + (i as u64), &mut buffer[(PAGE_SIZE * i)..(PAGE_SIZE * (i + 1))])?;
-      }
+    for (i, chunk) in buffer.chunks_mut(DMA_PAGES * PAGE_SIZE).enumerate() {
+      sum += self.read_pages(block + (i * DMA_PAGES) as u64, chunk)?;
     }
     Ok(sum)
   }
 
   fn write_at(&mut self, block: u64, buffer: &[u8]) -> Result<usize> {
     assert_eq!(buffer.len() % BLOCK_SIZE as usize, 0);
-    let page_num = buffer.len() / PAGE_SIZE;
     let mut sum = 0;
-    for i in 0..page_num {
-      if buffer.as_ptr() as usize % PAGE_SIZE == 0 {
-        sum += self.write_block_aligned(block + (i as u64), &buffer[(PAGE_SIZE * i)..(PAGE_SIZE * (i + 1))])?;
-      } else {
-        sum += self.write_block_unaligned(block + (i as u64), &buffer[(PAGE_SIZE * i)..(PAGE_SIZE * (i + 1))])?;
-      }
+    for (i, chunk) in buffer.chunks(DMA_PAGES * PAGE_SIZE).enumerate() {
+      sum += self.write_pages(block + (i * DMA_PAGES) as u64, chunk)?;
     }
     Ok(sum)
   }
@@ -1,10 +1,15 @@
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::cell::RefCell;
 use core::cmp::{max, min};
 
-use crate::libtrusted::mm::{virtual_alloc, virtual_free};
+use crate::libtrusted::mm::{Entry, virtual_alloc, virtual_free};
+use rpabi::PAGE_SIZE;
+use rpabi::pager::{PAGER_FAULT_EXEC, PAGER_FAULT_WRITE};
+use rpabi::vma::{VMA_PROT_EXEC, VMA_PROT_READ, VMA_PROT_WRITE};
 use redox::*;
 
 use crate::alloc::string::ToString;
@@ -19,6 +24,9 @@ pub trait Resource<D: Disk> {
   fn seek(&mut self, offset: isize, whence: usize, fs: &mut FileSystem<D>) -> Result<isize>;
   fn fmap(&mut self, map: &Map, fs: &mut FileSystem<D>) -> Result<usize>;
   fn funmap(&mut self, address: usize, fs: &mut FileSystem<D>) -> Result<usize>;
+  fn mmap(&mut self, asid: u16, map: &Map, pager: usize, cache: Rc<RefCell<PageCache>>) -> Result<usize>;
+  fn mfault(&mut self, key: (u16, usize), va: usize, access: usize, fs: &mut FileSystem<D>) -> Result<usize>;
+  fn munmap(&mut self, key: (u16, usize), fs: &mut FileSystem<D>) -> Result<usize>;
   fn fchmod(&mut self, mode: u16, fs: &mut FileSystem<D>) -> Result<usize>;
   fn fchown(&mut self, uid: u32, gid: u32, fs: &mut FileSystem<D>) -> Result<usize>;
   fn fcntl(&mut self, cmd: usize, arg: usize) -> Result<usize>;
@@ -102,6 +110,15 @@ impl<D: Disk> Resource<D> for DirResource {
   fn funmap(&mut self, _address: usize, _fs: &mut FileSystem<D>) -> Result<usize> {
     Err(Error::new(EBADF))
   }
+  fn mmap(&mut self, _asid: u16, _map: &Map, _pager: usize, _cache: Rc<RefCell<PageCache>>) -> Result<usize> {
+    Err(Error::new(EBADF))
+  }
+  fn mfault(&mut self, _key: (u16, usize), _va: usize, _access: usize, _fs: &mut FileSystem<D>) -> Result<usize> {
+    Err(Error::new(EBADF))
+  }
+  fn munmap(&mut self, _key: (u16, usize), _fs: &mut FileSystem<D>) -> Result<usize> {
+    Err(Error::new(EBADF))
+  }
 
   fn fchmod(&mut self, mode: u16, fs: &mut FileSystem<D>) -> Result<usize> {
     let mut node = fs.node(self.block)?;
@@ -254,6 +271,211 @@ impl Drop for Fmap {
   }
 }
 
+// page of a file read for its mappings
+struct CachedPage {
+  local: usize,
+  // written through some mapping since the last writeback, up to `len` bytes of it belong to the mappings
+  dirty: bool,
+  len: usize,
+  // client pages mapping it writable with the attribute that write protects them again
+  writers: Vec<(u16, usize, usize)>,
+}
+
+// pages of one file shared by all mappings of it, keyed by page number in the file
+// Note: dropped with the last mapping, which has written dirty pages back by then
+pub struct PageCache {
+  block: u64,
+  pages: BTreeMap<usize, CachedPage>,
+}
+
+impl PageCache {
+  pub fn new(block: u64) -> Self {
+    PageCache {
+      block,
+      pages: BTreeMap::new(),
+    }
+  }
+
+  // local address of page `page` of the file, read on first use
+  fn page<D: Disk>(&mut self, page: usize, fs: &mut FileSystem<D>) -> Result<usize> {
+    if let Some(cached) = self.pages.get(&page) {
+      return Ok(cached.local);
+    }
+    let local = virtual_alloc(1, true).ok_or(Error::new(ENOMEM))?;
+    let buf = unsafe { core::slice::from_raw_parts_mut(local as *mut u8, PAGE_SIZE) };
+    match fs.read_node(self.block, (page * PAGE_SIZE) as u64, buf) {
+      Ok(count) => buf[count..].fill(0),
+      Err(err) => {
+        virtual_free(local, 1);
+        return Err(err);
+      }
+    }
+    self.pages.insert(page, CachedPage { local, dirty: false, len: 0, writers: Vec::new() });
+    Ok(local)
+  }
+
+  // `page` is about to be mapped writable at `va` of `asid`
+  fn write(&mut self, page: usize, len: usize, writer: (u16, usize, usize)) {
+    if let Some(cached) = self.pages.get_mut(&page) {
+      cached.dirty = true;
+      cached.len = max(cached.len, len);
+      if !cached.writers.iter().any(|w| w.0 == writer.0 && w.1 == writer.1) {
+        cached.writers.push(writer);
+      }
+    }
+  }
+
+  // client pages of `[start, end)` in `asid` are going away
+  fn forget(&mut self, asid: u16, start: usize, end: usize) {
+    for cached in self.pages.values_mut() {
+      cached.writers.retain(|(a, va, _)| *a != asid || *va < start || *va >= end);
+    }
+  }
+
+  // write dirty pages in `[first, last)` back to the file
+  fn writeback<D: Disk>(&mut self, first: usize, last: usize, fs: &mut FileSystem<D>) -> Result<()> {
+    let mtime = crate::rtc::timestamp();
+    let mtime = core::time::Duration::from_secs(mtime);
+    for (page, cached) in self.pages.range_mut(first..last) {
+      if !cached.dirty {
+        continue;
+      }
+      // Note: write protect first, so that a write during writeback dirties the page again
+      for (asid, va, attr) in cached.writers.drain(..) {
+        let _ = rpsyscall::mem_map(0, cached.local, asid, va, attr);
+      }
+      cached.dirty = false;
+      let buf = unsafe { core::slice::from_raw_parts(cached.local as *const u8, cached.len) };
+      fs.write_node(
+        self.block,
+        (page * PAGE_SIZE) as u64,
+        buf,
+        mtime.as_secs(),
+        mtime.subsec_nanos(),
+      )?;
+    }
+    Ok(())
+  }
+}
+
+impl Drop for PageCache {
+  fn drop(&mut self) {
+    for cached in self.pages.values() {
+      virtual_free(cached.local, 1);
+    }
+  }
+}
+
+// file range mapped into a client address space, pages are taken from the page cache of the file
+// as the client faults on them
+pub struct ClientMap {
+  cache: Rc<RefCell<PageCache>>,
+  offset: usize,
+  size: usize,
+  flags: MapFlags,
+  asid: u16,
+  address: usize,
+}
+
+impl ClientMap {
+  // reserve the range in the client, with `pager` receiving its faults
+  pub fn new(cache: Rc<RefCell<PageCache>>, map: &Map, asid: u16, pager: usize) -> Result<Self> {
+    if map.size == 0 || map.address % PAGE_SIZE != 0 || map.offset % PAGE_SIZE != 0 {
+      return Err(Error::new(EINVAL));
+    }
+    if map.flags.contains(MAP_SHARED) == map.flags.contains(MAP_PRIVATE) {
+      return Err(Error::new(EINVAL));
+    }
+    let mut prot = 0;
+    if map.flags.contains(PROT_READ) {
+      prot |= VMA_PROT_READ;
+    }
+    if map.flags.contains(PROT_WRITE) {
+      prot |= VMA_PROT_WRITE;
+    }
+    if map.flags.contains(PROT_EXEC) {
+      prot |= VMA_PROT_EXEC;
+    }
+    let len = (map.size + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    rpsyscall::vma_reserve(asid, map.address, len, prot).map_err(|_| Error::new(EINVAL))?;
+    if rpsyscall::vma_pager(asid, map.address, len, pager).is_err() {
+      let _ = rpsyscall::vma_release(asid, map.address, len);
+      return Err(Error::new(ENOMEM));
+    }
+    Ok(Self {
+      cache,
+      offset: map.offset,
+      size: map.size,
+      flags: map.flags,
+      asid,
+      address: map.address,
+    })
+  }
+
+  fn pages(&self) -> usize {
+    (self.size + PAGE_SIZE - 1) / PAGE_SIZE
+  }
+
+  // page range of the file mapped
+  fn file_pages(&self) -> (usize, usize) {
+    let first = self.offset / PAGE_SIZE;
+    (first, first + self.pages())
+  }
+
+  fn shared_attribute(&self, writable: bool) -> usize {
+    Entry::new(writable, self.flags.contains(PROT_EXEC), false, true).attribute()
+  }
+
+  // map the page holding `va` to the client
+  pub fn fault<D: Disk>(&mut self, va: usize, access: usize, fs: &mut FileSystem<D>) -> Result<()> {
+    if va < self.address || (va - self.address) / PAGE_SIZE >= self.pages() {
+      return Err(Error::new(EFAULT));
+    }
+    // Note: a mapped page can always be read, so a mapping without `PROT_READ` gets no pages at all
+    if !self.flags.contains(PROT_READ)
+      || (access & PAGER_FAULT_WRITE != 0 && !self.flags.contains(PROT_WRITE))
+      || (access & PAGER_FAULT_EXEC != 0 && !self.flags.contains(PROT_EXEC)) {
+      return Err(Error::new(EFAULT));
+    }
+    let index = (va - self.address) / PAGE_SIZE;
+    let page = self.offset / PAGE_SIZE + index;
+    let client_va = self.address + index * PAGE_SIZE;
+    let mut cache = self.cache.borrow_mut();
+    let local = cache.page(page, fs)?;
+    let attr = if self.flags.contains(MAP_PRIVATE) {
+      // Note: the kernel copies a private page on its first write, the file never sees it
+      Entry::new(false, self.flags.contains(PROT_EXEC), self.flags.contains(PROT_WRITE), false).attribute()
+    } else if access & PAGER_FAULT_WRITE != 0 {
+      let len = min(PAGE_SIZE, self.size - index * PAGE_SIZE);
+      cache.write(page, len, (self.asid, client_va, self.shared_attribute(false)));
+      self.shared_attribute(true)
+    } else {
+      // Note: read-only until the first write, which faults again and marks the page dirty
+      self.shared_attribute(false)
+    };
+    rpsyscall::mem_map(0, local, self.asid, client_va, attr).map_err(|_| Error::new(ENOMEM))?;
+    Ok(())
+  }
+
+  // write dirty pages of a shared mapping back to the file
+  // Note: the cache is shared, pages dirtied through other mappings of the range are written as well
+  pub fn sync<D: Disk>(&mut self, fs: &mut FileSystem<D>) -> Result<()> {
+    if !self.flags.contains(MAP_SHARED) || !self.flags.contains(PROT_WRITE) {
+      return Ok(());
+    }
+    let (first, last) = self.file_pages();
+    self.cache.borrow_mut().writeback(first, last, fs)
+  }
+}
+
+impl Drop for ClientMap {
+  fn drop(&mut self) {
+    let len = self.pages() * PAGE_SIZE;
+    let _ = rpsyscall::vma_release(self.asid, self.address, len);
+    self.cache.borrow_mut().forget(self.asid, self.address, self.address + len);
+  }
+}
+
 pub struct FileResource {
   path: String,
   block: u64,
@@ -261,6 +483,8 @@ pub struct FileResource {
   seek: isize,
   uid: u32,
   fmaps: BTreeMap<usize, Fmap>,
+  // mappings in client address spaces keyed by asid and address
+  client_maps: BTreeMap<(u16, usize), ClientMap>,
 }
 
 impl FileResource {
@@ -272,6 +496,7 @@ impl FileResource {
       seek: 0,
       uid,
       fmaps: BTreeMap::new(),
+      client_maps: BTreeMap::new(),
     }
   }
 }
@@ -289,6 +514,7 @@ impl<D: Disk> Resource<D> for FileResource {
       seek: self.seek,
       uid: self.uid,
       fmaps: BTreeMap::new(),
+      client_maps: BTreeMap::new(),
     }).map(|b| b as Box<dyn Resource<D>>).map_err(|_| Error::new(ENOMEM))
   }
 
@@ -364,6 +590,39 @@ impl<D: Disk> Resource<D> for FileResource {
     }
   }
 
+  fn mmap(&mut self, asid: u16, map: &Map, pager: usize, cache: Rc<RefCell<PageCache>>) -> Result<usize> {
+    let accmode = self.flags & O_ACCMODE;
+    if map.flags.contains(PROT_READ) && !(accmode == O_RDWR || accmode == O_RDONLY) {
+      return Err(Error::new(EBADF));
+    }
+    // Note: writes to a private mapping never reach the file
+    if map.flags.contains(PROT_WRITE) && map.flags.contains(MAP_SHARED) && !(accmode == O_RDWR || accmode == O_WRONLY) {
+      return Err(Error::new(EBADF));
+    }
+
+    let map = ClientMap::new(cache, map, asid, pager)?;
+    let address = map.address;
+    self.client_maps.insert((asid, address), map);
+    Ok(address)
+  }
+
+  fn mfault(&mut self, key: (u16, usize), va: usize, access: usize, fs: &mut FileSystem<D>) -> Result<usize> {
+    match self.client_maps.get_mut(&key) {
+      Some(map) => map.fault(va, access, fs).map(|_| 0),
+      None => Err(Error::new(EFAULT)),
+    }
+  }
+
+  fn munmap(&mut self, key: (u16, usize), fs: &mut FileSystem<D>) -> Result<usize> {
+    if let Some(mut map) = self.client_maps.remove(&key) {
+      map.sync(fs)?;
+
+      Ok(0)
+    } else {
+      Err(Error::new(EINVAL))
+    }
+  }
+
   fn fchmod(&mut self, mode: u16, fs: &mut FileSystem<D>) -> Result<usize> {
     let mut node = fs.node(self.block)?;
 
@@ -449,6 +708,10 @@ impl<D: Disk> Resource<D> for FileResource {
       fmap.sync(fs)?;
     }
 
+    for map in self.client_maps.values_mut() {
+      map.sync(fs)?;
+    }
+
     Ok(0)
   }
 
@@ -1,5 +1,6 @@
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
+use alloc::rc::{Rc, Weak};
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
@@ -23,6 +24,10 @@ pub struct FileScheme<D: Disk> {
   next_id: AtomicUsize,
   files: RefCell<BTreeMap<usize, Box<dyn Resource<D>>>>,
   fmap: RefCell<BTreeMap<usize, usize>>,
+  // file id of each mapping in a client address space, keyed by asid and address
+  client_maps: RefCell<BTreeMap<(u16, usize), usize>>,
+  // page cache of each mapped file keyed by block, owned by its mappings
+  page_caches: RefCell<BTreeMap<u64, Weak<RefCell<PageCache>>>>,
 }
 
 impl<D: Disk> FileScheme<D> {
@@ -33,6 +38,72 @@ impl<D: Disk> FileScheme<D> {
       next_id: AtomicUsize::new(1),
       files: RefCell::new(BTreeMap::new()),
       fmap: RefCell::new(BTreeMap::new()),
+      client_maps: RefCell::new(BTreeMap::new()),
+      page_caches: RefCell::new(BTreeMap::new()),
+    }
+  }
+
+  /// map a file range into address space `asid`, faults on it are sent to the endpoint `pager`
+  pub fn mmap(&self, asid: u16, id: usize, map: &Map, pager: usize) -> Result<usize> {
+    let mut files = self.files.borrow_mut();
+    if let Some(file) = files.get_mut(&id) {
+      let cache = self.page_cache(file.block());
+      let address = file.mmap(asid, map, pager, cache)?;
+      self.client_maps.borrow_mut().insert((asid, address), id);
+      Ok(address)
+    } else {
+      Err(Error::new(EBADF))
+    }
+  }
+
+  // page cache shared by all mappings of the file at `block`
+  fn page_cache(&self, block: u64) -> Rc<RefCell<PageCache>> {
+    let mut caches = self.page_caches.borrow_mut();
+    if let Some(cache) = caches.get(&block).and_then(|cache| cache.upgrade()) {
+      return cache;
+    }
+    caches.retain(|_, cache| cache.strong_count() != 0);
+    let cache = Rc::new(RefCell::new(PageCache::new(block)));
+    caches.insert(block, Rc::downgrade(&cache));
+    cache
+  }
+
+  /// resolve a fault of address space `asid` at `va` on a mapped file
+  pub fn page_fault(&self, asid: u16, va: usize, access: usize) -> Result<usize> {
+    let (key, id) = match self.client_maps.borrow().range(..=(asid, va)).next_back() {
+      Some((key, id)) if key.0 == asid => (*key, *id),
+      _ => return Err(Error::new(EFAULT)),
+    };
+    let mut files = self.files.borrow_mut();
+    if let Some(file) = files.get_mut(&id) {
+      file.mfault(key, va, access, &mut self.fs.borrow_mut())
+    } else {
+      Err(Error::new(EBADF))
+    }
+  }
+
+  /// write back and remove the mapping at `address` of address space `asid`
+  pub fn munmap(&self, asid: u16, address: usize) -> Result<usize> {
+    if let Some(id) = self.client_maps.borrow_mut().remove(&(asid, address)) {
+      let mut files = self.files.borrow_mut();
+      if let Some(file) = files.get_mut(&id) {
+        file.munmap((asid, address), &mut self.fs.borrow_mut())
+      } else {
+        Err(Error::new(EINVAL))
+      }
+    } else {
+      Err(Error::new(EINVAL))
+    }
+  }
+
+  /// write back and remove all mappings of address space `asid`, which is going away
+  pub fn client_exit(&self, asid: u16) {
+    let keys: Vec<(u16, usize)> = self.client_maps.borrow()
+      .range((asid, 0)..=(asid, usize::MAX))
+      .map(|(key, _)| *key)
+      .collect();
+    for (asid, address) in keys {
+      let _ = self.munmap(asid, address);
     }
   }
 
@@ -748,16 +819,18 @@ impl<D: Disk> Scheme for FileScheme<D> {
     }
   }
 
-  //TODO: implement
-  fn funmap(&self, address: usize, length: usize) -> Result<usize> {
-    println!("redoxfs: funmap 0x{:X}, {}", address, length);
-    Err(Error::new(ENOSYS))
+  // Note: a mapping is removed as a whole, `length` is not checked
+  fn funmap(&self, address: usize, _length: usize) -> Result<usize> {
+    self.funmap_old(address)
   }
 
   fn close(&self, id: usize) -> Result<usize> {
     // println!("Close {}", id);
     let mut files = self.files.borrow_mut();
-    if files.remove(&id).is_some() {
+    if let Some(mut file) = files.remove(&id) {
+      // Note: mappings left by the client are written back and removed with the file
+      self.client_maps.borrow_mut().retain(|_, file_id| *file_id != id);
+      let _ = file.sync(&mut self.fs.borrow_mut());
       Ok(0)
     } else {
       Err(Error::new(EBADF))
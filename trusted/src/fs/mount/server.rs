@@ -1,4 +1,5 @@
 use alloc::string::String;
+use core::mem::size_of;
 
 use spin::Once;
 
@@ -11,15 +12,21 @@ use redox::*;
 use crate::fs::{FileSystem, VirtioClient};
 use crate::fs::mount::scheme::FileScheme;
 
-fn redoxfs(msg: Message, client_tid: usize) -> usize {
+// Note: `asid` is the badge of the caller's capability, set by the kernel
+fn redoxfs(msg: Message, asid: usize) -> usize {
   let mut packet = Packet::default();
   packet.a = msg.a;
   packet.b = msg.b;
   packet.c = msg.c;
   packet.d = msg.d;
-  let asid = get_asid(client_tid).unwrap();
+  let asid = asid as u16;
   if asid == get_asid(0).unwrap() {
-    FILE_SCHEME.get().unwrap().handle(&mut packet);
+    if packet.a == rpservapi::fs::action::CLIENT_EXIT {
+      FILE_SCHEME.get().unwrap().client_exit(packet.b as u16);
+      packet.a = 0;
+    } else {
+      FILE_SCHEME.get().unwrap().handle(&mut packet);
+    }
   } else {
     let fs = match packet.a {
       SYS_OPEN
@@ -32,7 +39,6 @@ fn redoxfs(msg: Message, client_tid: usize) -> usize {
         Some(s)
       }
       SYS_DUP
-      | SYS_READ
       | SYS_WRITE
       | SYS_FMAP_OLD
       | SYS_FMAP
@@ -46,7 +52,9 @@ fn redoxfs(msg: Message, client_tid: usize) -> usize {
         packet.c = s.local_start;
         Some(s)
       }
-      SYS_LSEEK
+      // Note: the buffer arrives as a long message, `c` and `d` are already local
+      SYS_READ
+      | SYS_LSEEK
       | SYS_FCHMOD
       | SYS_FCHOWN
       | SYS_FCNTL
@@ -59,14 +67,44 @@ fn redoxfs(msg: Message, client_tid: usize) -> usize {
       => None,
       _ => panic!("NOT translated packet"),
     };
-    FILE_SCHEME.get().unwrap().handle(&mut packet);
+    let scheme = FILE_SCHEME.get().unwrap();
+    match packet.a {
+      // Note: mappings of other address spaces are served page by page as their pager
+      SYS_FMAP => {
+        packet.a = if packet.d >= size_of::<Map>() {
+          let map = unsafe { &*(packet.c as *const Map) };
+          Error::mux(scheme.mmap(asid, packet.b, map, *PAGER.get().unwrap()))
+        } else {
+          Error::mux(Err(Error::new(EFAULT)))
+        };
+      }
+      SYS_FUNMAP => packet.a = Error::mux(scheme.munmap(asid, packet.b)),
+      _ => scheme.handle(&mut packet),
+    }
     drop(fs);
   }
+  // Note: the whole buffer is copied back to the client, clear what an earlier read left past the end
+  if msg.a == SYS_READ {
+    let n = Error::demux(packet.a).unwrap_or(0).min(msg.d);
+    unsafe { core::ptr::write_bytes((msg.c + n) as *mut u8, 0, msg.d - n); }
+  }
   packet.a
 }
 
+// resolve a fault on a mapped file, see `rpabi::pager`
+fn pager(msg: Message, _badge: usize) -> usize {
+  let (va, access, asid) = (msg.a, msg.b, msg.c as u16);
+  Error::mux(FILE_SCHEME.get().unwrap().page_fault(asid, va, access))
+}
+
 static FILE_SCHEME: Once<FileScheme<VirtioClient>> = Once::new();
 
+// Note: clients are badged with their asid, faults arrive through a capability with this badge
+const PAGER_BADGE: usize = usize::MAX;
+
+// capability handed to the kernel for mapped files
+static PAGER: Once<usize> = Once::new();
+
 pub fn server() {
   info!("server started t{}", get_tid());
   rpsyscall::server_register(rpabi::server::SERVER_REDOX_FS).unwrap();
@@ -74,9 +112,20 @@ pub fn server() {
   match FileSystem::open(disk, Some(0)) {
     Ok(filesystem) => {
       FILE_SCHEME.call_once(|| { FileScheme::new(String::from("virtio"), filesystem) });
+      PAGER.call_once(|| {
+        let endpoint = rpsyscall::endpoint_create().unwrap();
+        rpsyscall::cap_mint(endpoint, rpabi::cap::CAP_RIGHT_SEND, PAGER_BADGE).unwrap()
+      });
+      // Note: reads land here and are copied back to the client on reply
+      let buffer = alloc::vec![0u8; rpabi::itc::ITC_LONG_MAX].leak();
+      rpsyscall::itc_buffer_set(buffer.as_ptr() as usize, buffer.len(), 0, 0).unwrap();
       loop {
-        let (client_tid, msg) = Message::receive().unwrap();
-        let r = request_wrapper(redoxfs, msg, client_tid).unwrap();
+        let (client_tid, badge, msg) = Message::receive_badged().unwrap();
+        let r = if badge == PAGER_BADGE {
+          request_wrapper(pager, msg, badge).unwrap()
+        } else {
+          request_wrapper(redoxfs, msg, badge).unwrap()
+        };
         let result = Message::new(r, 0, 0, 0);
         let _ = result.send_to(client_tid);
       }
@@ -144,21 +144,39 @@ impl PageAttribute for Entry {
   }
 }
 
+// level-1 or level-2 entry mapping a block instead of pointing to a table
+fn is_block(e: usize) -> bool {
+  e & 0b1 != 0 && e & 0b1110 != 0
+}
+
+// entry of the 4 KB page holding `va` in a block of `size`
+fn page_of(e: usize, va: usize, size: usize) -> Entry {
+  let mut entry = Entry(e);
+  entry.set_address(entry.address() + (va & (size - 1) & !(PAGE_SIZE - 1)));
+  entry
+}
+
 pub fn query(va: usize) -> Option<Entry> {
   let l1x = (va >> PAGE_TABLE_L1_SHIFT) & (PAGE_SIZE / core::mem::size_of::<usize>() - 1);
   let l2x = (va >> PAGE_TABLE_L2_SHIFT) & (PAGE_SIZE / core::mem::size_of::<usize>() - 1);
   let l3x = (va >> PAGE_TABLE_L3_SHIFT) & (PAGE_SIZE / core::mem::size_of::<usize>() - 1);
-  if read_directory_entry(l1x) & 0b1 != 0 {
-    if read_level_1_entry(l1x, l2x) & 0b1 != 0 {
-      let r = read_level_2_entry(l1x, l2x, l3x);
-      if r & 0b1 != 0 {
-        Some(Entry(r))
-      } else {
-        None
-      }
-    } else {
-      None
-    }
+  let l1e = read_directory_entry(l1x);
+  if l1e & 0b1 == 0 {
+    return None;
+  }
+  if is_block(l1e) {
+    return Some(page_of(l1e, va, 1 << PAGE_TABLE_L1_SHIFT));
+  }
+  let l2e = read_level_1_entry(l1x, l2x);
+  if l2e & 0b1 == 0 {
+    return None;
+  }
+  if is_block(l2e) {
+    return Some(page_of(l2e, va, 1 << PAGE_TABLE_L2_SHIFT));
+  }
+  let r = read_level_2_entry(l1x, l2x, l3x);
+  if r & 0b1 != 0 {
+    Some(Entry(r))
   } else {
     None
   }
@@ -175,7 +193,7 @@ pub fn traverse<F>(limit: usize, f: F) where F: Fn(usize, Entry) -> () {
       if va >= limit {
         return;
       }
-      let l2e = read_level_1_entry(l1x, l2x);
+      let l2e = if is_block(l1e) { l1e } else { read_level_1_entry(l1x, l2x) };
       if l2e & 0b1 == 0 {
         continue;
       }
@@ -184,6 +202,14 @@ pub fn traverse<F>(limit: usize, f: F) where F: Fn(usize, Entry) -> () {
         if va >= limit {
           return;
         }
+        if is_block(l1e) {
+          f(va, page_of(l1e, va, 1 << PAGE_TABLE_L1_SHIFT));
+          continue;
+        }
+        if is_block(l2e) {
+          f(va, page_of(l2e, va, 1 << PAGE_TABLE_L2_SHIFT));
+          continue;
+        }
         let l3e = read_level_2_entry(l1x, l2x, l3x);
         if l3e & 0b1 != 0 {
           f(va, Entry(l3e));
@@ -191,4 +217,4 @@ pub fn traverse<F>(limit: usize, f: F) where F: Fn(usize, Entry) -> () {
       }
     }
   }
-}
\ No newline at end of file
+}
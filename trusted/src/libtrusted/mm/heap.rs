@@ -3,10 +3,9 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 
 use buddy_system_allocator::LockedHeapWithRescue;
 use rpabi::PAGE_SIZE;
+use rpabi::vma::{VMA_PROT_READ, VMA_PROT_WRITE};
 use spin::Mutex;
 
-use rpsyscall::mem_alloc;
-
 use crate::libtrusted::mm::default_page_attribute;
 
 #[global_allocator]
@@ -14,21 +13,18 @@ static HEAP_ALLOCATOR: LockedHeapWithRescue<32> = LockedHeapWithRescue::new(enla
 
 static HEAP_TOP: AtomicUsize = AtomicUsize::new(0);
 
+// Note: heap areas are reserved, their pages are allocated on first touch
 fn enlarge(heap: &mut buddy_system_allocator::Heap<32>, _layout: &Layout) {
   const HEAP_DELTA_SIZE: usize = 16;
   let delta = HEAP_TOP.fetch_add(PAGE_SIZE * HEAP_DELTA_SIZE, Ordering::Relaxed);
-  for i in 0..HEAP_DELTA_SIZE {
-    mem_alloc(0, delta + i * PAGE_SIZE, default_page_attribute()).expect("heap enlarge allocate memory failed");
-  }
+  rpsyscall::vma_reserve(0, delta, HEAP_DELTA_SIZE * PAGE_SIZE, VMA_PROT_READ | VMA_PROT_WRITE).expect("heap enlarge reserve failed");
   // info!("Enlarge heap {:x} ~ {:x}", delta, delta + PAGE_SIZE * HEAP_DELTA_SIZE);
   unsafe { heap.add_to_heap(delta, delta + PAGE_SIZE * HEAP_DELTA_SIZE); }
 }
 
 pub fn init() {
   const HEAP_INIT_SIZE: usize = 16;
-  for i in 0..HEAP_INIT_SIZE {
-    mem_alloc(0, rpabi::CONFIG_HEAP_BTM + i * PAGE_SIZE, default_page_attribute()).expect("heap init allocate mem failed");
-  }
+  rpsyscall::vma_reserve(0, rpabi::CONFIG_HEAP_BTM, HEAP_INIT_SIZE * PAGE_SIZE, VMA_PROT_READ | VMA_PROT_WRITE).expect("heap init reserve failed");
   HEAP_TOP.store(rpabi::CONFIG_HEAP_BTM + HEAP_INIT_SIZE * PAGE_SIZE, Ordering::Relaxed);
   unsafe {
     HEAP_ALLOCATOR.lock().init(rpabi::CONFIG_HEAP_BTM, HEAP_INIT_SIZE * PAGE_SIZE);
@@ -37,22 +33,47 @@ pub fn init() {
 
 static VIRTUAL_HEAP_BASE: Mutex<usize> = Mutex::new(rpabi::CONFIG_VIRTUAL_HEAP_BTM);
 
+// large buffers are mapped with 2 MB blocks where possible
+const VIRTUAL_BLOCK_SIZE: usize = 1 << rpabi::PAGE_TABLE_L2_SHIFT;
+
 pub fn virtual_alloc(num_of_page: usize, alloc_physical: bool) -> Option<usize> {
+  let size = num_of_page * PAGE_SIZE;
   let mut base = VIRTUAL_HEAP_BASE.lock();
+  if alloc_physical && size >= VIRTUAL_BLOCK_SIZE {
+    *base = (*base + VIRTUAL_BLOCK_SIZE - 1) & !(VIRTUAL_BLOCK_SIZE - 1);
+  }
   let addr = *base;
-  *base += num_of_page * PAGE_SIZE;
+  *base += size;
   drop(base);
   if alloc_physical {
-    for i in 0..num_of_page {
-      let r = rpsyscall::mem_alloc(0, addr + i * PAGE_SIZE, default_page_attribute());
-      if r.is_err() {
-        error!("virtual_alloc mem_alloc failed");
+    let mut offset = 0;
+    while offset < size {
+      let hint = if size - offset >= VIRTUAL_BLOCK_SIZE { VIRTUAL_BLOCK_SIZE } else { PAGE_SIZE };
+      match rpsyscall::mem_alloc_hint(0, addr + offset, default_page_attribute(), hint) {
+        Ok(mapped) => offset += mapped,
+        Err(_) => {
+          error!("virtual_alloc mem_alloc failed");
+          offset += PAGE_SIZE;
+        }
       }
     }
   }
   Some(addr)
 }
 
+// `num_of_page` physically contiguous pages for device access, returns their address and physical address
+pub fn dma_alloc(num_of_page: usize) -> Option<(usize, usize)> {
+  let va = virtual_alloc(num_of_page, false)?;
+  match rpsyscall::mem_alloc_dma(0, va, num_of_page * PAGE_SIZE, default_page_attribute()) {
+    Ok(pa) => Some((va, pa)),
+    Err(_) => {
+      error!("dma_alloc mem_alloc_dma failed");
+      None
+    }
+  }
+}
+
+// Note: unmapping any page of a block unmaps the whole block
 pub fn virtual_free(va: usize, num_of_page: usize) {
   for i in 0..num_of_page {
     let _ = rpsyscall::mem_unmap(0, va + i * PAGE_SIZE);
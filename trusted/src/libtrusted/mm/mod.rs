@@ -1,4 +1,4 @@
-pub use heap::{virtual_alloc, virtual_free};
+pub use heap::{dma_alloc, virtual_alloc, virtual_free};
 pub use heap::init as heap_init;
 pub use page_table::Entry;
 pub use page_table::query;
@@ -19,6 +19,7 @@ unsafe impl Sync for Thread {}
 pub type IoResult<T> = core::result::Result<T, ()>; // alias of io::Result
 
 const THREAD_STACK_PAGE_NUM: usize = 48;
+const JOIN_POLL_INTERVAL_US: usize = 10000;
 
 impl Thread {
   pub unsafe fn new(p: Box<dyn FnOnce()>) -> IoResult<Thread> {
@@ -58,7 +59,7 @@ impl Thread {
         virtual_free(self.stack_btm, self.stack_size_in_page);
         break;
       } else {
-        rpsyscall::thread_yield();
+        rpsyscall::sleep(JOIN_POLL_INTERVAL_US);
       }
     }
   }
@@ -3,8 +3,9 @@ use crate::libtrusted::wrapper::request_wrapper;
 use rpsyscall::get_tid;
 use rpsyscall::message::Message;
 
-fn mm(msg: Message, tid: usize) -> usize {
-  let asid = rpsyscall::get_asid(tid).unwrap();
+// Note: `asid` is the badge of the caller's capability, set by the kernel
+fn mm(msg: Message, asid: usize) -> usize {
+  let asid = asid as u16;
   match msg.a {
     rpservapi::mm::action::ALLOC => {
       match rpsyscall::mem_alloc(asid, msg.b, default_page_attribute()) {
@@ -22,8 +23,8 @@ pub fn server() {
   info!("server started t{}",  get_tid());
   rpsyscall::server_register(rpabi::server::SERVER_MM).unwrap();
   loop {
-    let (client_tid, msg) = Message::receive().unwrap();
-    let r = request_wrapper(mm, msg, client_tid).unwrap();
+    let (client_tid, asid, msg) = Message::receive_badged().unwrap();
+    let r = request_wrapper(mm, msg, asid).unwrap();
     let result = Message::new(r, 0, 0, 0);
     let _ = result.send_to(client_tid);
   }
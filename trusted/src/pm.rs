@@ -7,7 +7,7 @@ use spin::Mutex;
 
 use crate::libtrusted::foreign_slice::ForeignSlice;
 use crate::libtrusted::wrapper::request_wrapper;
-use rpsyscall::{get_asid, get_tid};
+use rpsyscall::get_tid;
 use rpsyscall::message::Message;
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
@@ -18,6 +18,9 @@ enum ProcessStatus {
 
 static PID_ALLOCATOR: AtomicUsize = AtomicUsize::new(200);
 
+// pages of memory a process may map unless spawned with another limit, 64 MB
+const DEFAULT_PAGE_LIMIT: usize = 16384;
+
 struct Process {
   pid: usize,
   parent: Option<usize>,
@@ -53,6 +56,11 @@ impl ProcessManager {
     pid as usize
   }
 
+  fn command(&self, asid: u16) -> Option<String> {
+    let map = self.list.lock();
+    map.values().find(|p| p.asid == asid && p.status == ProcessStatus::Running).map(|p| p.command.clone())
+  }
+
   fn poll_exit(&self, pid: usize) -> bool {
     let mut map = self.list.lock();
     if let Some(p) = map.get_mut(&pid) {
@@ -61,6 +69,7 @@ impl ProcessManager {
       } else {
         if let Ok(_) = rpsyscall::event_wait(rpabi::event::EVENT_THREAD_EXIT, p.main_tid) {
           p.status = ProcessStatus::Exited;
+          client_exit(p.asid);
           rpsyscall::address_space_destroy(p.asid).expect("process address space destroy failed");
           return true;
         } else {
@@ -73,15 +82,32 @@ impl ProcessManager {
 
   fn ps(&self) {
     let map = self.list.lock();
-    println!("PID\t\tSTATUS\t\tTID\t\tPASID\t\tASID\t\tCOMMAND");
+    println!("PID\t\tSTATUS\t\tTID\t\tPASID\t\tASID\t\tCYCLES\t\tSWITCHES\tCORE\t\tANON\t\tSHARED\t\tDEVICE\t\tLIMIT\t\tCOMMAND");
     for pid in map.keys() {
       if let Some(p) = map.get(pid) {
-        println!("{}\t\t{:?}\t\t{}\t\t{:?}\t\t{}\t\t{}",
+        // Note: statistics are gone once main thread exited
+        let (cycles, switches, core) = match rpsyscall::thread_stat(p.main_tid) {
+          Ok((_, switches, core, as_cycles)) => (as_cycles, switches, core),
+          Err(_) => (0, 0, 0),
+        };
+        // Note: in pages, gone with the address space
+        let (anonymous, shared, device, limit) = match p.status {
+          ProcessStatus::Running => rpsyscall::address_space_stat(p.asid).unwrap_or((0, 0, 0, 0)),
+          ProcessStatus::Exited => (0, 0, 0, 0),
+        };
+        println!("{}\t\t{:?}\t\t{}\t\t{:?}\t\t{}\t\t{}\t\t{}\t\t{}\t\t{}\t\t{}\t\t{}\t\t{}\t\t{}",
                  p.pid,
                  p.status,
                  p.main_tid,
                  p.parent,
                  p.asid,
+                 cycles,
+                 switches,
+                 core,
+                 anonymous,
+                 shared,
+                 device,
+                 limit,
                  p.command,
         );
       }
@@ -91,8 +117,42 @@ impl ProcessManager {
 
 static PROCESS_MANAGER: ProcessManager = ProcessManager::new();
 
-fn pm(msg: Message, tid: usize) -> (usize, usize) {
-  let asid = get_asid(tid).unwrap();
+// tell servers keeping state of address space `asid` it is going away
+// Note: its id is recycled once destroyed, servers know their clients by the id in the badge
+fn client_exit(asid: u16) {
+  // Note: mapped files are written back while the pages are still there
+  let _ = Message::new(rpservapi::fs::action::CLIENT_EXIT, asid as usize, 0, 0)
+    .call(rpabi::server::SERVER_REDOX_FS);
+  let _ = Message::new(rpservapi::terminal::action::CLIENT_EXIT, asid as usize, 0, 0)
+    .call(rpabi::server::SERVER_TERMINAL);
+}
+
+// page limit of address space `asid`, a process never gives a child more than its own
+fn page_limit(asid: u16) -> Result<usize, &'static str> {
+  rpsyscall::address_space_stat(asid).map(|(_, _, _, limit)| limit).map_err(|_e| "address space stat failed")
+}
+
+// start `entry(arg)` on stack `sp` in a copy-on-write duplicate of address space `asid`
+fn fork(asid: u16, entry: usize, sp: usize, arg: usize) -> Result<usize, &'static str> {
+  let limit = page_limit(asid)?;
+  let child_asid = rpsyscall::address_space_fork(asid).map_err(|_e| "address space fork failed")?;
+  let tid = match rpsyscall::address_space_limit(child_asid, limit)
+    .and_then(|_| rpsyscall::thread_alloc(child_asid, entry, sp, arg)) {
+    Ok(tid) => tid,
+    Err(_) => {
+      let _ = rpsyscall::address_space_destroy(child_asid);
+      return Err("thread alloc failed");
+    }
+  };
+  let command = PROCESS_MANAGER.command(asid).unwrap_or_else(|| String::from("fork"));
+  let pid = PROCESS_MANAGER.register(child_asid, tid, Some(asid as usize), command);
+  rpsyscall::thread_set_status(tid, rpabi::thread::THREAD_STATUS_RUNNABLE).expect("pm start thread failed");
+  Ok(pid)
+}
+
+// Note: `asid` is the badge of the caller's capability, set by the kernel
+fn pm(msg: Message, asid: usize) -> (usize, usize) {
+  let asid = asid as u16;
   match msg.a {
     rpservapi::pm::action::SPAWN => {
       let length = msg.c;
@@ -102,11 +162,17 @@ fn pm(msg: Message, tid: usize) -> (usize, usize) {
       if length >= 128 {
         return (rpservapi::pm::result::INVARG, 0);
       }
+      let limit = match page_limit(asid) {
+        Ok(own) if msg.d == 0 => DEFAULT_PAGE_LIMIT.min(own),
+        Ok(own) if msg.d <= own => msg.d,
+        _ => return (rpservapi::pm::result::INVARG, 0),
+      };
       let s = ForeignSlice::new(asid, msg.b, msg.c).unwrap();
       let cmd = s.local_slice();
       let cmd = core::str::from_utf8(cmd);
       if let Ok(cmd) = cmd {
         if let Ok((child_asid, tid)) = crate::libtrusted::loader::spawn(cmd) {
+          rpsyscall::address_space_limit(child_asid, limit).expect("pm set process limit failed");
           let pid = PROCESS_MANAGER.register(child_asid, tid, Some(asid as usize), String::from(cmd));
           rpsyscall::thread_set_status(tid, rpabi::thread::THREAD_STATUS_RUNNABLE).expect("pm start thread failed");
           (rpservapi::pm::result::OK, pid)
@@ -129,6 +195,12 @@ fn pm(msg: Message, tid: usize) -> (usize, usize) {
       PROCESS_MANAGER.ps();
       (rpservapi::pm::result::OK, 0)
     }
+    rpservapi::pm::action::FORK => {
+      match fork(asid, msg.b, msg.c, msg.d) {
+        Ok(pid) => (rpservapi::pm::result::OK, pid),
+        Err(_) => (rpservapi::pm::result::FORK_FAILED, 0),
+      }
+    }
     _ => {
       (rpservapi::pm::result::INVARG, 0)
     }
@@ -139,8 +211,8 @@ pub fn server() {
   info!("server started t{}", get_tid());
   rpsyscall::server_register(rpabi::server::SERVER_PM).unwrap();
   loop {
-    let (client_tid, msg) = Message::receive().unwrap();
-    let (a, b) = request_wrapper(pm, msg, client_tid).unwrap();
+    let (client_tid, asid, msg) = Message::receive_badged().unwrap();
+    let (a, b) = request_wrapper(pm, msg, asid).unwrap();
     let result = Message::new(a, b, 0, 0);
     let _ = result.send_to(client_tid);
   }
@@ -1,21 +1,29 @@
+use rpabi::thread::{THREAD_PRIORITY_DRIVER, THREAD_PRIORITY_SERVER};
+
 use crate::libtrusted::thread;
 use crate::libtrusted::wrapper::server_wrapper;
 
 pub fn main() {
   let mut join_handlers = vec![];
 
-  join_handlers.push(thread::spawn(|| {
+  let blk = thread::spawn(|| {
     server_wrapper(crate::blk::server);
-  }));
+  });
+  rpsyscall::thread_set_priority(blk.native(), THREAD_PRIORITY_DRIVER).expect("root set blk priority failed");
+  // Note: kernel routes device interrupts to core 0
+  rpsyscall::thread_set_affinity(blk.native(), 1 << 0).expect("root set blk affinity failed");
+  join_handlers.push(blk);
 
   #[cfg(not(feature = "tx2"))]
   join_handlers.push(thread::spawn(|| {
     server_wrapper(crate::rtc::server);
   }));
 
-  join_handlers.push(thread::spawn(|| {
+  let fs = thread::spawn(|| {
     server_wrapper(crate::fs::server);
-  }));
+  });
+  rpsyscall::thread_set_priority(fs.native(), THREAD_PRIORITY_SERVER).expect("root set fs priority failed");
+  join_handlers.push(fs);
 
   join_handlers.push(thread::spawn(|| {
     server_wrapper(crate::terminal::input_server);
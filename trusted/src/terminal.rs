@@ -1,17 +1,24 @@
 use alloc::collections::VecDeque;
 
+use rpabi::PAGE_SIZE;
 use spin::{Mutex, Once};
 
-use rpsyscall::get_tid;
+use rpservapi::terminal::{CHANNEL_PAGES, CHANNEL_VA};
+use rpsyscall::channel::Producer;
+use rpsyscall::{get_asid, get_tid};
 use rpsyscall::message::Message;
 
+const INPUT_POLL_INTERVAL_US: usize = 10000;
+
 pub fn input_server() {
   loop {
     if let Ok(c) = rpsyscall::getc() {
       let mut buf = buffer().lock();
       buf.push_back(c);
+    } else {
+      rpsyscall::sleep(INPUT_POLL_INTERVAL_US);
     }
-    rpsyscall::thread_yield();
+    flush();
   }
 }
 
@@ -24,19 +31,100 @@ fn buffer() -> &'static Mutex<VecDeque<u8>> {
   }
 }
 
+// client which claimed input last, its channel is mapped at `CHANNEL_VA` here
+struct Reader {
+  asid: u16,
+  producer: Producer<u8>,
+}
+
+static READER: Mutex<Option<Reader>> = Mutex::new(None);
+
+// move buffered input into the channel of the reader, as much as fits
+// Note: input stays buffered while there is no reader
+fn flush() {
+  let reader = READER.lock();
+  if let Some(reader) = reader.as_ref() {
+    let mut buf = buffer().lock();
+    while let Some(c) = buf.front() {
+      match reader.producer.try_push(*c) {
+        Ok(true) => { buf.pop_front(); }
+        _ => break,
+      }
+    }
+  }
+}
+
+// make `asid` the reader, returns the notifications of its new channel, none if it keeps its channel
+fn claim(asid: u16, connected: bool) -> Result<Option<(usize, usize)>, rpsyscall::Error> {
+  let mut reader = READER.lock();
+  if connected && reader.as_ref().map_or(false, |r| r.asid == asid) {
+    return Ok(None);
+  }
+  // Note: the previous reader keeps its end of the channel, it just gets no more input
+  if reader.take().is_some() {
+    for i in 0..CHANNEL_PAGES {
+      let _ = rpsyscall::mem_unmap(0, CHANNEL_VA + i * PAGE_SIZE);
+    }
+  }
+  // Note: the client may still map the channel of an earlier claim
+  for i in 0..CHANNEL_PAGES {
+    let _ = rpsyscall::mem_unmap(asid, CHANNEL_VA + i * PAGE_SIZE);
+  }
+  let (data, space, peer_data, peer_space) = rpsyscall::channel_create(CHANNEL_VA, CHANNEL_PAGES, asid, CHANNEL_VA)?;
+  *reader = Some(Reader {
+    asid,
+    producer: unsafe { Producer::new(CHANNEL_VA, CHANNEL_PAGES, data, space) },
+  });
+  Ok(Some((peer_data, peer_space)))
+}
+
+// drop the channel of `asid` if it is the reader, before its id is handed to another address space
+fn client_exit(asid: u16) {
+  let mut reader = READER.lock();
+  if reader.as_ref().map_or(false, |r| r.asid == asid) {
+    reader.take();
+    for i in 0..CHANNEL_PAGES {
+      let _ = rpsyscall::mem_unmap(0, CHANNEL_VA + i * PAGE_SIZE);
+    }
+  }
+}
+
+// Note: `asid` is the badge of the caller's capability, set by the kernel
+fn terminal(msg: Message, asid: usize) -> Message {
+  let mut result = Message::default();
+  match msg.a {
+    rpservapi::terminal::action::CLAIM => {
+      match claim(asid as u16, msg.b != 0) {
+        Ok(None) => { result.a = rpservapi::terminal::result::OK; }
+        Ok(Some((data, space))) => {
+          result.a = rpservapi::terminal::result::CONNECTED;
+          result.b = data;
+          result.c = space;
+        }
+        Err(_) => { result.a = rpservapi::terminal::result::ERR; }
+      }
+      flush();
+    }
+    rpservapi::terminal::action::CLIENT_EXIT if asid as u16 == get_asid(0).unwrap() => {
+      client_exit(msg.b as u16);
+      result.a = rpservapi::terminal::result::OK;
+    }
+    _ => { result.a = rpservapi::terminal::result::ERR; }
+  }
+  result
+}
+
 pub fn server() {
   info!("server started t{}",  get_tid());
   rpsyscall::server_register(rpabi::server::SERVER_TERMINAL).unwrap();
-  let mut client_tid;
-  client_tid = Message::receive().unwrap().0;
+  let mut request = Message::receive_badged().unwrap();
   loop {
-    let mut msg = rpsyscall::message::Message::default();
-    let mut buf = buffer().lock();
-    match buf.pop_front() {
-      None => { msg.a = 0 }
-      Some(c) => { msg.a = c as usize }
-    }
-    drop(buf);
-    client_tid = msg.reply_recv(client_tid).unwrap().0;
+    let (client_tid, asid, msg) = request;
+    let result = terminal(msg, asid);
+    request = match result.reply_recv_badged(client_tid) {
+      Ok(request) => request,
+      // Note: the client exited or its call timed out, nothing was received along with the reply
+      Err(_) => Message::receive_badged().unwrap(),
+    };
   }
-}
\ No newline at end of file
+}